@@ -1,9 +1,323 @@
-use clap::Parser;
+use std::path::PathBuf;
+
+use chrono::{DateTime, FixedOffset};
+use clap::{Parser, Subcommand};
+
+use paclogrs::{date::parse_date, ColorMode, LogPoint, PacmanAction, Period};
+
+use crate::output::{Column, OutputFormat};
 
 #[derive(Parser, Debug)]
 #[clap(name = "paclogrs", version)]
 #[clap(about = "Pacman log but prettier", long_about = None)]
 pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
     #[clap(help = "Packages to list (supports *-glob)")]
     pub packages: Vec<String>,
+
+    #[clap(
+        short = 'f',
+        long = "log-file",
+        value_name = "PATH",
+        global = true,
+        help = "Log file to read, `-` for stdin (can be repeated, defaults to piped stdin or pacman.conf's LogFile)"
+    )]
+    pub log_files: Vec<PathBuf>,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Do not read rotated copies of the log files (pacman.log.1, pacman.log.2.gz, ...)"
+    )]
+    pub no_rotated: bool,
+
+    #[clap(
+        long,
+        value_name = "DATE",
+        global = true,
+        parse(try_from_str = parse_date),
+        help = "Only show changes at or after DATE (e.g. `2021-03-04`, `yesterday`, `2 weeks ago`, `last boot`)"
+    )]
+    pub since: Option<DateTime<FixedOffset>>,
+
+    #[clap(
+        long,
+        value_name = "DATE",
+        global = true,
+        parse(try_from_str = parse_date),
+        help = "Only show changes at or before DATE"
+    )]
+    pub until: Option<DateTime<FixedOffset>>,
+
+    #[clap(
+        short = 'a',
+        long = "action",
+        value_name = "ACTIONS",
+        global = true,
+        use_value_delimiter = true,
+        require_value_delimiter = true,
+        help = "Only show these actions (comma-separated: installed,upgraded,downgraded,removed,reinstalled)"
+    )]
+    pub actions: Vec<PacmanAction>,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Only show installed packages (same as --action installed)"
+    )]
+    pub installed: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Only show upgraded packages (same as --action upgraded)"
+    )]
+    pub upgraded: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Only show downgraded packages (same as --action downgraded)"
+    )]
+    pub downgraded: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Only show removed packages (same as --action removed)"
+    )]
+    pub removed: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Only show reinstalled packages (same as --action reinstalled)"
+    )]
+    pub reinstalled: bool,
+
+    #[clap(long, help = "Group changes by pacman transaction")]
+    pub transactions: bool,
+
+    #[clap(
+        short = 'c',
+        long = "command",
+        value_name = "TEXT",
+        global = true,
+        allow_hyphen_values = true,
+        help = "Only show changes made by a pacman command containing TEXT (can be repeated, e.g. `-Syu`)"
+    )]
+    pub commands: Vec<String>,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Show the pacman command that made each change"
+    )]
+    pub show_command: bool,
+
+    #[clap(
+        short = 'o',
+        long,
+        arg_enum,
+        global = true,
+        default_value = "text",
+        help = "Output format"
+    )]
+    pub output: OutputFormat,
+
+    #[clap(
+        long,
+        value_name = "WHEN",
+        global = true,
+        default_value = "auto",
+        possible_values = ["auto", "always", "never"],
+        help = "Color the text output (auto honors NO_COLOR, CLICOLOR_FORCE and pacman.conf's Color)"
+    )]
+    pub color: ColorMode,
+
+    #[clap(
+        long,
+        arg_enum,
+        value_name = "COLUMNS",
+        use_value_delimiter = true,
+        require_value_delimiter = true,
+        help = "Comma-separated columns of the csv/tsv output [default: timestamp,action,name,previous_version,current_version]"
+    )]
+    pub columns: Vec<Column>,
+
+    #[clap(
+        short = 'F',
+        long,
+        help = "Keep watching the log file and show new changes as they are logged (text and ndjson output only)"
+    )]
+    pub follow: bool,
+
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = &["last", "follow"],
+        help = "Only show the N oldest changes (transactions with --transactions)"
+    )]
+    pub first: Option<usize>,
+
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with = "follow",
+        help = "Only show the N most recent changes (transactions with --transactions)"
+    )]
+    pub last: Option<usize>,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Report corrupt package changes with their line number and exit with an error"
+    )]
+    pub strict: bool,
+
+    #[clap(
+        long,
+        global = true,
+        help = "Only show upgrades and downgrades that vercmp disagrees with"
+    )]
+    pub contradictions: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Show every change of a package, how long each version stayed installed and its current
+    /// version
+    History {
+        #[clap(help = "Exact package name (no glob)")]
+        package: String,
+    },
+    /// List the packages installed at a given time according to the log
+    State {
+        #[clap(
+            long,
+            value_name = "DATE",
+            parse(try_from_str = parse_date),
+            help = "Point in time to rebuild the package list at (e.g. `2021-03-04 12:00`, `last boot`)"
+        )]
+        at: DateTime<FixedOffset>,
+
+        #[clap(
+            long,
+            help = "Only print package names, for `pacman -S --needed - < list`"
+        )]
+        names_only: bool,
+    },
+    /// Show the net package changes between two points of the log, intermediate versions
+    /// collapsed
+    Diff {
+        #[clap(help = "Start point: a date or a transaction id (`42` or `#42`)")]
+        from: LogPoint,
+
+        #[clap(help = "End point: a date or a transaction id")]
+        to: LogPoint,
+    },
+    /// Find the cached package files bringing the system back to an earlier point and print
+    /// the pacman commands to do it
+    Rollback {
+        #[clap(
+            long,
+            value_name = "POINT",
+            help = "Point to roll back to: a date or a transaction id (`42` or `#42`)"
+        )]
+        to: LogPoint,
+    },
+    /// Show how many changes each action made, the most upgraded packages, upgrades over time
+    /// and transaction sizes (honors --since, --until, --action, --command and --contradictions)
+    Stats {
+        #[clap(
+            long,
+            default_value = "month",
+            possible_values = ["month", "week"],
+            help = "Period to count upgrades over"
+        )]
+        per: Period,
+
+        #[clap(
+            long,
+            value_name = "N",
+            default_value = "10",
+            help = "Number of most upgraded packages to show"
+        )]
+        top: usize,
+    },
+}
+
+impl Command {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::History { .. } => "history",
+            Self::State { .. } => "state",
+            Self::Diff { .. } => "diff",
+            Self::Rollback { .. } => "rollback",
+            Self::Stats { .. } => "stats",
+        }
+    }
+}
+
+impl Cli {
+    /// Actions selected through `--action` and its shorthand flags
+    pub fn actions(&self) -> Vec<PacmanAction> {
+        let mut actions = self.actions.clone();
+        for (flag, action) in [
+            (self.installed, PacmanAction::Installed),
+            (self.upgraded, PacmanAction::Upgraded),
+            (self.downgraded, PacmanAction::Downgraded),
+            (self.removed, PacmanAction::Removed),
+            (self.reinstalled, PacmanAction::Reinstalled),
+        ] {
+            if flag && !actions.contains(&action) {
+                actions.push(action);
+            }
+        }
+        actions
+    }
+
+    /// Top-level options set on the command line that the subcommand would not apply
+    pub fn ignored_options(&self) -> Vec<&'static str> {
+        let filters = [
+            (!self.packages.is_empty(), "PACKAGES"),
+            (self.since.is_some(), "--since"),
+            (self.until.is_some(), "--until"),
+            (
+                !self.actions().is_empty(),
+                "--action or its shorthand flags",
+            ),
+            (!self.commands.is_empty(), "--command"),
+            (self.contradictions, "--contradictions"),
+        ];
+        let modes = [
+            (self.transactions, "--transactions"),
+            (self.follow, "--follow"),
+            (self.first.is_some(), "--first"),
+            (self.last.is_some(), "--last"),
+        ];
+
+        let options = match self.command {
+            None => return Vec::new(),
+            // Stats are computed over the filtered changes
+            Some(Command::Stats { .. }) => &modes[..],
+            Some(_) => &[&filters[..], &modes[..]].concat(),
+        };
+        options
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, option)| *option)
+            .collect()
+    }
+
+    /// Columns selected through `--columns`, or the default ones
+    pub fn columns(&self) -> &[Column] {
+        if self.columns.is_empty() {
+            Column::DEFAULT
+        } else {
+            &self.columns
+        }
+    }
 }
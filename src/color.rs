@@ -0,0 +1,53 @@
+use std::{env, str::FromStr};
+
+use anyhow::anyhow;
+use termcolor::ColorChoice;
+
+use crate::pacman_conf::PacmanConf;
+
+/// When to color the text output, as given to `--color`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorMode {
+    type Err = anyhow::Error;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(anyhow!("`{mode}` is not a valid color mode!")),
+        }
+    }
+}
+
+impl ColorMode {
+    /// Resolve the mode into a [`ColorChoice`] for stdout
+    ///
+    /// In `Auto` mode, a non-empty `NO_COLOR` disables colors and a `CLICOLOR_FORCE` other than
+    /// `0` forces them. Otherwise colors are only used on terminals, and only if pacman.conf
+    /// enables `Color` when it could be read.
+    pub fn color_choice(self, conf: &PacmanConf) -> ColorChoice {
+        match self {
+            Self::Always => ColorChoice::Always,
+            Self::Never => ColorChoice::Never,
+            Self::Auto if env_is_set("NO_COLOR", "") => ColorChoice::Never,
+            Self::Auto if env_is_set("CLICOLOR_FORCE", "0") => ColorChoice::Always,
+            Self::Auto if !atty::is(atty::Stream::Stdout) || conf.color == Some(false) => {
+                ColorChoice::Never
+            }
+            // Still lets termcolor turn colors off for dumb terminals
+            Self::Auto => ColorChoice::Auto,
+        }
+    }
+}
+
+/// Whether the environment variable `name` is set to anything but `unset_value`
+fn env_is_set(name: &str, unset_value: &str) -> bool {
+    env::var_os(name).is_some_and(|value| value != unset_value)
+}
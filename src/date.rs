@@ -0,0 +1,102 @@
+use std::fs;
+
+use anyhow::anyhow;
+use anyhow::Result as AnyResult;
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, LocalResult, Months, NaiveDate, NaiveDateTime,
+    TimeZone, Utc,
+};
+
+const ABSOLUTE_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"];
+
+fn local(naive: NaiveDateTime) -> AnyResult<DateTime<FixedOffset>> {
+    match naive.and_local_timezone(Local) {
+        LocalResult::Single(datetime) | LocalResult::Ambiguous(datetime, _) => {
+            Ok(datetime.fixed_offset())
+        }
+        LocalResult::None => Err(anyhow!("`{naive}` does not exist in the local timezone")),
+    }
+}
+
+fn midnight(days_ago: i64) -> AnyResult<DateTime<FixedOffset>> {
+    let date = Local::now().date_naive() - Duration::days(days_ago);
+    local(date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+/// Time the system booted, from `btime` in /proc/stat
+fn last_boot() -> AnyResult<DateTime<FixedOffset>> {
+    let stat = fs::read_to_string("/proc/stat")?;
+    let btime = stat
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .ok_or(anyhow!("No boot time found in /proc/stat"))?
+        .trim()
+        .parse()?;
+
+    Utc.timestamp_opt(btime, 0)
+        .single()
+        .map(|datetime| datetime.with_timezone(&Local).fixed_offset())
+        .ok_or(anyhow!("Invalid boot time `{btime}`"))
+}
+
+/// `<n> <unit>s ago`
+fn relative(expr: &str) -> Option<AnyResult<DateTime<FixedOffset>>> {
+    let mut words = expr.split_whitespace();
+    let (count, unit) = (words.next()?.parse::<u32>().ok()?, words.next()?);
+    if words.next() != Some("ago") || words.next().is_some() {
+        return None;
+    }
+
+    let now = Local::now().fixed_offset();
+    let n = i64::from(count);
+    let datetime = match unit.trim_end_matches('s') {
+        "second" | "sec" => now.checked_sub_signed(Duration::seconds(n)),
+        "minute" | "min" => now.checked_sub_signed(Duration::minutes(n)),
+        "hour" => now.checked_sub_signed(Duration::hours(n)),
+        "day" => now.checked_sub_signed(Duration::days(n)),
+        "week" => now.checked_sub_signed(Duration::weeks(n)),
+        "month" => now.checked_sub_months(Months::new(count)),
+        "year" => count
+            .checked_mul(12)
+            .and_then(|months| now.checked_sub_months(Months::new(months))),
+        _ => return Some(Err(anyhow!("Unknown time unit `{unit}`"))),
+    };
+
+    Some(datetime.ok_or(anyhow!("`{expr}` is out of range")))
+}
+
+/// Parse a point in time given on the command line
+///
+/// Accepts absolute dates (`2021-03-04`, `2021-03-04 12:00`, RFC 3339, ...) in local time unless
+/// an offset is given, as well as `now`, `today`, `yesterday`, `last boot` and `<n> <unit>s ago`.
+pub fn parse_date(expr: &str) -> AnyResult<DateTime<FixedOffset>> {
+    let expr = expr.trim();
+    match expr.to_lowercase().as_str() {
+        "now" => return Ok(Local::now().fixed_offset()),
+        "today" => return midnight(0),
+        "yesterday" => return midnight(1),
+        "last boot" | "boot" => return last_boot(),
+        lower => {
+            if let Some(datetime) = relative(lower) {
+                return datetime;
+            }
+        }
+    }
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(expr) {
+        return Ok(datetime);
+    }
+    if let Ok(datetime) = DateTime::parse_from_str(expr, "%Y-%m-%dT%H:%M:%S%z") {
+        return Ok(datetime);
+    }
+    for format in ABSOLUTE_FORMATS {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(expr, format) {
+            return local(naive);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(expr, "%Y-%m-%d") {
+        return local(date.and_hms_opt(0, 0, 0).unwrap());
+    }
+
+    Err(anyhow!("`{expr}` is not a valid date"))
+}
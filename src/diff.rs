@@ -0,0 +1,170 @@
+use anyhow::Result as AnyResult;
+use serde::Serialize;
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::{state::PackageState, version::Version};
+
+/// Net change of a single package between two states
+#[derive(Debug, Serialize)]
+pub struct PackageDiff {
+    name: String,
+    /// `None` if the package was added
+    from: Option<Version>,
+    /// `None` if the package was removed
+    to: Option<Version>,
+}
+
+impl PackageDiff {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn from(&self) -> Option<&Version> {
+        self.from.as_ref()
+    }
+
+    pub fn to(&self) -> Option<&Version> {
+        self.to.as_ref()
+    }
+}
+
+/// Net difference between two [`PackageState`]s, intermediate versions collapsed
+///
+/// # Serialization schema
+///
+/// | Field        | Type  | Description                                        |
+/// |--------------|-------|----------------------------------------------------|
+/// | `added`      | array | Packages only installed in the second state        |
+/// | `removed`    | array | Packages only installed in the first state         |
+/// | `upgraded`   | array | Packages with a newer version in the second state  |
+/// | `downgraded` | array | Packages with an older version in the second state |
+///
+/// Each entry has a `name`, a `from` version (`null` when added) and a `to` version (`null`
+/// when removed). Entries are sorted by name.
+#[derive(Debug, Default, Serialize)]
+pub struct StateDiff {
+    added: Vec<PackageDiff>,
+    removed: Vec<PackageDiff>,
+    upgraded: Vec<PackageDiff>,
+    downgraded: Vec<PackageDiff>,
+}
+
+impl StateDiff {
+    /// Packages versions compare with `vercmp`, those it deems equal are left out
+    pub fn new(from: &PackageState, to: &PackageState) -> Self {
+        let mut diff = Self::default();
+        let entry = |name: &str, from: Option<&Version>, to: Option<&Version>| PackageDiff {
+            name: name.to_string(),
+            from: from.cloned(),
+            to: to.cloned(),
+        };
+
+        for (name, old) in from.packages() {
+            match to.get(name) {
+                None => diff.removed.push(entry(name, Some(old), None)),
+                Some(new) if new > old => diff.upgraded.push(entry(name, Some(old), Some(new))),
+                Some(new) if new < old => diff.downgraded.push(entry(name, Some(old), Some(new))),
+                Some(_) => {}
+            }
+        }
+        for (name, new) in to.packages() {
+            if from.get(name).is_none() {
+                diff.added.push(entry(name, None, Some(new)));
+            }
+        }
+
+        diff
+    }
+
+    pub fn added(&self) -> &[PackageDiff] {
+        &self.added
+    }
+
+    pub fn removed(&self) -> &[PackageDiff] {
+        &self.removed
+    }
+
+    pub fn upgraded(&self) -> &[PackageDiff] {
+        &self.upgraded
+    }
+
+    pub fn downgraded(&self) -> &[PackageDiff] {
+        &self.downgraded
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.upgraded.is_empty()
+            && self.downgraded.is_empty()
+    }
+}
+
+impl StateDiff {
+    pub fn render<W: WriteColor>(&self, out: &mut W) -> AnyResult<()> {
+        let sections = [
+            ("Added", Color::Green, self.added()),
+            ("Removed", Color::Red, self.removed()),
+            ("Upgraded", Color::Cyan, self.upgraded()),
+            ("Downgraded", Color::Magenta, self.downgraded()),
+        ];
+        let mut first = true;
+        for (title, color, entries) in sections {
+            if entries.is_empty() {
+                continue;
+            }
+            if !first {
+                out.write_all(b"\n")?;
+            }
+            first = false;
+
+            out.set_color(ColorSpec::new().set_bold(true))?;
+            out.write_all(format!("{title} ({})\n", entries.len()).as_bytes())?;
+
+            let width = entries
+                .iter()
+                .map(|entry| entry.name().len())
+                .max()
+                .unwrap_or_default();
+            for entry in entries {
+                out.set_color(
+                    ColorSpec::new()
+                        .set_fg(Some(Color::Yellow))
+                        .set_bold(true)
+                        .set_intense(true),
+                )?;
+                out.write_all(format!("    {:width$}", entry.name()).as_bytes())?;
+                out.reset()?;
+                out.write_all(b"  ")?;
+
+                if let Some(from) = entry.from() {
+                    // Removed packages only have their old version, shown in the section color
+                    let from_color = if entry.to().is_some() {
+                        Color::Magenta
+                    } else {
+                        color
+                    };
+                    out.set_color(ColorSpec::new().set_fg(Some(from_color)))?;
+                    out.write_all(from.as_str().as_bytes())?;
+                }
+                if entry.from().is_some() && entry.to().is_some() {
+                    out.reset()?;
+                    out.write_all(b" -> ")?;
+                }
+                if let Some(to) = entry.to() {
+                    out.set_color(ColorSpec::new().set_fg(Some(color)))?;
+                    out.write_all(to.as_str().as_bytes())?;
+                }
+
+                out.reset()?;
+                out.write_all(b"\n")?;
+            }
+        }
+
+        if first {
+            out.write_all(b"No difference\n")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+/// Why a log line did not produce a [`PackageChange`](crate::PackageChange)
+#[derive(Debug, Error)]
+pub enum LineError {
+    /// Valid package change rejected by the [`ChangeFilter`](crate::ChangeFilter)
+    #[error("package change does not match the filter")]
+    Filtered,
+    /// Any other kind of line: transaction markers, hooks, scriptlet output, ...
+    #[error("not a package change")]
+    NotPackageChange,
+    /// Looks like a package change but cannot be parsed
+    #[error("corrupt package change: {0}")]
+    Corrupt(String),
+}
+
+/// Corrupt package change found while reading a log in strict mode
+#[derive(Debug, Error)]
+#[error("line {line_number}: {reason}: `{line}`")]
+pub struct CorruptLine {
+    /// 1-based
+    pub line_number: usize,
+    pub line: String,
+    pub reason: String,
+}
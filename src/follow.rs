@@ -0,0 +1,126 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::Context;
+use anyhow::Result as AnyResult;
+use inotify::{EventMask, Inotify, WatchMask};
+
+use crate::paclog::{ends_command, parse_command, ChangeFilter, PackageChange};
+
+/// Reads whatever was appended to the log since the last call
+struct Tail {
+    reader: BufReader<File>,
+    /// Incomplete last line, waiting for its newline, as raw bytes since scriptlet output may
+    /// not be valid UTF-8
+    pending: Vec<u8>,
+    command: Option<String>,
+}
+
+impl Tail {
+    fn open(path: &Path) -> AnyResult<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Unable to open `{}`", path.display()))?;
+        Ok(Self {
+            reader: BufReader::new(file),
+            pending: Vec::new(),
+            command: None,
+        })
+    }
+
+    /// Start over if the file was truncated below what has already been read
+    fn rewind_if_truncated(&mut self) -> AnyResult<()> {
+        let position = self.reader.stream_position()?;
+        if self.reader.get_ref().metadata()?.len() < position {
+            self.reader.seek(SeekFrom::Start(0))?;
+            self.pending.clear();
+        }
+        Ok(())
+    }
+
+    fn read_changes<F>(&mut self, filter: &ChangeFilter, on_change: &mut F) -> AnyResult<()>
+    where
+        F: FnMut(PackageChange) -> AnyResult<()>,
+    {
+        loop {
+            if self.reader.read_until(b'\n', &mut self.pending)? == 0
+                || !self.pending.ends_with(b"\n")
+            {
+                return Ok(());
+            }
+
+            // Same decoding as `LogLines`
+            let bytes = std::mem::take(&mut self.pending);
+            let line = String::from_utf8_lossy(&bytes);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if let Some(running) = parse_command(line) {
+                self.command = Some(running);
+                continue;
+            }
+            if ends_command(line) {
+                self.command = None;
+                continue;
+            }
+            if !filter.command_matches(self.command.as_deref()) {
+                continue;
+            }
+
+            if let Ok(change) = PackageChange::from_line(line.to_string(), filter) {
+                on_change(change.with_command(self.command.clone()))?;
+            }
+        }
+    }
+}
+
+/// Print the changes of the log at `path`, then keep waiting for new ones like `tail -F`
+///
+/// Survives the log being truncated or replaced by a new file (e.g. by logrotate). Never returns
+/// unless an error occurs.
+pub fn follow<F>(path: &Path, filter: &ChangeFilter, mut on_change: F) -> AnyResult<()>
+where
+    F: FnMut(PackageChange) -> AnyResult<()>,
+{
+    let file_name = path.file_name().context("Log file has no name")?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    // Watching the directory rather than the file catches the log being rotated
+    let mut inotify = Inotify::init()?;
+    inotify.watches().add(
+        dir,
+        WatchMask::MODIFY | WatchMask::CREATE | WatchMask::MOVED_TO,
+    )?;
+
+    let mut tail = Tail::open(path)?;
+    tail.read_changes(filter, &mut on_change)?;
+
+    let mut buffer = [0; 4096];
+    loop {
+        let mut replaced = false;
+        for event in inotify.read_events_blocking(&mut buffer)? {
+            if event.name != Some(file_name) {
+                continue;
+            }
+            if event
+                .mask
+                .intersects(EventMask::CREATE | EventMask::MOVED_TO)
+            {
+                replaced = true;
+            }
+        }
+
+        tail.rewind_if_truncated()?;
+        tail.read_changes(filter, &mut on_change)?;
+
+        if replaced {
+            let command = tail.command.take();
+            tail = Tail::open(path)?;
+            tail.command = command;
+            tail.read_changes(filter, &mut on_change)?;
+        }
+    }
+}
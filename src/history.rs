@@ -0,0 +1,234 @@
+use anyhow::Result as AnyResult;
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use serde::{Serialize, Serializer};
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::{
+    paclog::{PackageChange, PacmanAction},
+    version::Version,
+};
+
+fn serialize_seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_i64(duration.num_seconds())
+}
+
+/// Human-readable rounded duration (`3d 4h`, `2y 10d`, ...)
+pub fn format_duration(duration: Duration) -> String {
+    const UNITS: &[(&str, i64)] = &[
+        ("y", 365 * 24 * 3600),
+        ("d", 24 * 3600),
+        ("h", 3600),
+        ("m", 60),
+        ("s", 1),
+    ];
+
+    let mut seconds = duration.num_seconds().max(0);
+    let parts: Vec<String> = UNITS
+        .iter()
+        .filter_map(|&(unit, length)| {
+            let count = seconds / length;
+            seconds %= length;
+            (count > 0).then(|| format!("{count}{unit}"))
+        })
+        // Two units are precise enough
+        .take(2)
+        .collect();
+
+    if parts.is_empty() {
+        String::from("0s")
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Period during which a version of a package stayed installed
+#[derive(Debug, Serialize)]
+pub struct VersionLifetime {
+    version: Version,
+    installed: DateTime<FixedOffset>,
+    /// `None` if still installed according to the log
+    replaced: Option<DateTime<FixedOffset>>,
+    /// Up to now if still installed, in seconds when serialized
+    #[serde(serialize_with = "serialize_seconds")]
+    duration: Duration,
+}
+
+impl VersionLifetime {
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    pub fn installed(&self) -> &DateTime<FixedOffset> {
+        &self.installed
+    }
+
+    pub fn replaced(&self) -> Option<&DateTime<FixedOffset>> {
+        self.replaced.as_ref()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Timeline of a single package
+///
+/// # Serialization schema
+///
+/// | Field             | Type           | Description                                            |
+/// |-------------------|----------------|--------------------------------------------------------|
+/// | `name`            | string         | Package name                                           |
+/// | `changes`         | array          | [`PackageChange`]s of the package                      |
+/// | `lifetimes`       | array          | [`VersionLifetime`]s, oldest first                     |
+/// | `current_version` | string \| null | Version installed according to the log                 |
+/// | `upgrades`        | integer        | Number of upgrades                                     |
+/// | `downgrades`      | integer        | Number of downgrades                                   |
+///
+/// A version lifetime has a `version`, an `installed` RFC 3339 timestamp, a `replaced` one
+/// (`null` while still installed) and a `duration` in seconds.
+#[derive(Debug, Serialize)]
+pub struct PackageHistory {
+    name: String,
+    changes: Vec<PackageChange>,
+    lifetimes: Vec<VersionLifetime>,
+    current_version: Option<Version>,
+    upgrades: usize,
+    downgrades: usize,
+}
+
+impl PackageHistory {
+    /// Build the history of `name` out of its changes in chronological order
+    pub fn new(name: &str, changes: Vec<PackageChange>) -> Self {
+        let now = Local::now().fixed_offset();
+        let mut lifetimes: Vec<VersionLifetime> = Vec::new();
+        let mut installed: Option<(Version, DateTime<FixedOffset>)> = None;
+
+        for change in &changes {
+            // Reinstalling keeps the same version around
+            if change.action() == PacmanAction::Reinstalled && installed.is_some() {
+                continue;
+            }
+
+            if let Some((version, since)) = installed.take() {
+                lifetimes.push(VersionLifetime {
+                    version,
+                    installed: since,
+                    replaced: Some(*change.datetime()),
+                    duration: *change.datetime() - since,
+                });
+            }
+            installed = change
+                .current_version()
+                .map(|version| (version.clone(), *change.datetime()));
+        }
+
+        let current_version = installed.as_ref().map(|(version, _)| version.clone());
+        if let Some((version, since)) = installed {
+            lifetimes.push(VersionLifetime {
+                version,
+                installed: since,
+                replaced: None,
+                duration: now - since,
+            });
+        }
+
+        let count = |action| changes.iter().filter(|c| c.action() == action).count();
+        Self {
+            name: name.to_string(),
+            upgrades: count(PacmanAction::Upgraded),
+            downgrades: count(PacmanAction::Downgraded),
+            changes,
+            lifetimes,
+            current_version,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn changes(&self) -> &[PackageChange] {
+        &self.changes
+    }
+
+    pub fn lifetimes(&self) -> &[VersionLifetime] {
+        &self.lifetimes
+    }
+
+    /// Version installed at the end of the log, `None` if removed or never installed
+    pub fn current_version(&self) -> Option<&Version> {
+        self.current_version.as_ref()
+    }
+
+    pub fn upgrades(&self) -> usize {
+        self.upgrades
+    }
+
+    pub fn downgrades(&self) -> usize {
+        self.downgrades
+    }
+}
+
+impl PackageHistory {
+    pub fn render<W: WriteColor>(&self, out: &mut W, show_command: bool) -> AnyResult<()> {
+        for change in self.changes() {
+            change.render(out, show_command)?;
+        }
+
+        out.set_color(ColorSpec::new().set_bold(true))?;
+        out.write_all(b"\nVersions\n")?;
+        out.reset()?;
+
+        let width = self
+            .lifetimes()
+            .iter()
+            .map(|lifetime| lifetime.version().as_str().len())
+            .max()
+            .unwrap_or_default();
+        for lifetime in self.lifetimes() {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+            out.write_all(format!("    {:width$}", lifetime.version().as_str()).as_bytes())?;
+
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            let until = lifetime.replaced().map_or(String::from("now"), |replaced| {
+                replaced.format("%Y-%m-%d %H:%M").to_string()
+            });
+            out.write_all(
+                format!(
+                    "  {} -> {until:16}  ",
+                    lifetime.installed().format("%Y-%m-%d %H:%M")
+                )
+                .as_bytes(),
+            )?;
+
+            out.reset()?;
+            out.write_all(format!("{}\n", format_duration(lifetime.duration())).as_bytes())?;
+        }
+
+        out.set_color(ColorSpec::new().set_bold(true))?;
+        out.write_all(b"\nCurrent version: ")?;
+        out.reset()?;
+        match self.current_version() {
+            Some(version) => {
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+                out.write_all(version.as_str().as_bytes())?;
+            }
+            None => {
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
+                out.write_all(b"not installed")?;
+            }
+        }
+        out.reset()?;
+
+        out.write_all(
+            format!(
+                "\nUpgrades: {}, downgrades: {}\n",
+                self.upgrades(),
+                self.downgrades()
+            )
+            .as_bytes(),
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+//! Parser and query API for pacman's log file
+//!
+//! ```no_run
+//! use paclogrs::{package_glob, read_changes, ChangeFilter, LogSource, PacmanAction};
+//!
+//! let filter = ChangeFilter {
+//!     packages: vec![package_glob("linux*").unwrap()],
+//!     actions: vec![PacmanAction::Upgraded],
+//!     ..Default::default()
+//! };
+//! let sources = LogSource::File("/var/log/pacman.log".into()).with_rotated();
+//!
+//! for change in read_changes(&sources, &filter, false) {
+//!     let change = change.unwrap();
+//!     println!("{} {:?}", change.name(), change.current_version());
+//! }
+//! ```
+
+pub mod color;
+pub mod date;
+pub mod diff;
+pub mod error;
+pub mod follow;
+pub mod history;
+pub mod paclog;
+pub mod pacman_conf;
+pub mod reverse;
+pub mod rollback;
+pub mod source;
+pub mod state;
+pub mod stats;
+pub mod transaction;
+pub mod version;
+
+pub use color::ColorMode;
+pub use diff::{PackageDiff, StateDiff};
+pub use error::{CorruptLine, LineError};
+pub use history::{PackageHistory, VersionLifetime};
+pub use paclog::{
+    get_changes, package_glob, read_changes, ChangeFilter, ChangeReader, PackageChange,
+    PacmanAction,
+};
+pub use pacman_conf::PacmanConf;
+pub use reverse::{read_changes_rev, ReverseChangeReader};
+pub use rollback::{PackageCache, RollbackPlan, RollbackTarget};
+pub use source::LogSource;
+pub use state::{LogPoint, PackageState};
+pub use stats::{ActionCounts, PackageActivity, Period, PeriodCount, Stats};
+pub use transaction::{get_transactions, Transaction, TransactionReader, TransactionStatus};
+pub use version::Version;
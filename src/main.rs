@@ -1,27 +1,366 @@
 mod cli;
-mod paclog;
+mod output;
 
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    io::{self, Write},
+};
+
+use chrono::{DateTime, FixedOffset};
 use clap::StructOpt;
 
+use anyhow::bail;
 use anyhow::Result as AnyResult;
 
-use cli::Cli;
-use paclog::get_changes;
+use cli::{Cli, Command};
+use output::{write_csv, write_json, write_json_record, write_ndjson_record, OutputFormat};
+use paclogrs::{
+    follow::follow, package_glob, read_changes, read_changes_rev, ChangeFilter, CorruptLine,
+    LogPoint, LogSource, PackageCache, PackageChange, PackageHistory, PackageState, PacmanConf,
+    RollbackPlan, StateDiff, Stats, Transaction, TransactionReader,
+};
 use regex::Regex;
+use termcolor::{BufferedStandardStream, WriteColor};
 
 fn main() -> AnyResult<()> {
     let args = Cli::parse();
+    let conf = PacmanConf::load();
+
+    // Every text, JSON and CSV output goes through this single buffered stream
+    let mut out = BufferedStandardStream::stdout(args.color.color_choice(&conf));
+    match run(&args, &conf, &mut out).and_then(|()| Ok(out.flush()?)) {
+        // The reader went away, e.g. `paclogrs | head`
+        Err(e) if is_broken_pipe(&e) => Ok(()),
+        result => result,
+    }
+}
+
+fn run(args: &Cli, conf: &PacmanConf, out: &mut impl WriteColor) -> AnyResult<()> {
+    if let (Some(command), [first, ..]) = (&args.command, &args.ignored_options()[..]) {
+        bail!(
+            "{first} cannot be used with the {} subcommand",
+            command.name()
+        );
+    }
 
     let regexes = args
         .packages
         .iter()
         // Allow glob/regex with star
-        .map(|s| Regex::new(&format!("^{}$", regex::escape(s).replace(r"\*", ".*"))))
+        .map(|s| package_glob(s))
         .collect::<Result<Vec<Regex>, regex::Error>>()?;
 
-    for change in get_changes(regexes)? {
-        change.print()?;
+    let filter = ChangeFilter {
+        packages: regexes,
+        actions: args.actions(),
+        since: args.since,
+        until: args.until,
+        commands: args.commands.clone(),
+        contradictions_only: args.contradictions,
+    };
+
+    let sources = log_sources(args, conf);
+    let corrupt = Cell::new(0);
+
+    match &args.command {
+        Some(Command::History { package }) => {
+            history(args, &sources, package, out, &corrupt)?;
+            return check_corrupt(&corrupt);
+        }
+        Some(Command::State { at, names_only }) => {
+            state(args, &sources, at, *names_only, out, &corrupt)?;
+            return check_corrupt(&corrupt);
+        }
+        Some(Command::Diff { from, to }) => {
+            diff(args, &sources, from, to, out, &corrupt)?;
+            return check_corrupt(&corrupt);
+        }
+        Some(Command::Rollback { to }) => {
+            rollback(args, &sources, to, conf, out, &corrupt)?;
+            return check_corrupt(&corrupt);
+        }
+        Some(Command::Stats { per, top }) => {
+            let transactions = TransactionReader::new(&sources, &filter).strict(args.strict);
+            let transactions =
+                report_corrupt(transactions, &corrupt).collect::<AnyResult<Vec<_>>>()?;
+            let stats = Stats::new(&transactions, *per, *top);
+            match args.output {
+                OutputFormat::Text => stats.render(out)?,
+                OutputFormat::Csv | OutputFormat::Tsv => {
+                    bail!("stats does not support csv/tsv output")
+                }
+                format => write_json_record(out, &stats, format)?,
+            }
+            return check_corrupt(&corrupt);
+        }
+        None => {}
+    }
+
+    if args.follow {
+        // Not a clap conflict: --strict is global and --follow does not exist in subcommands
+        if args.strict {
+            bail!("--strict cannot be used with --follow");
+        }
+        if args.transactions || !matches!(args.output, OutputFormat::Text | OutputFormat::Ndjson) {
+            bail!("--follow only supports text and ndjson output of individual changes");
+        }
+        let (path, rotated) = match (args.log_files.len() <= 1, sources.split_last()) {
+            (true, Some((LogSource::File(path), rotated))) => (path, rotated),
+            _ => bail!("--follow needs a single log file"),
+        };
+
+        let mut emit = |change: PackageChange| {
+            match args.output {
+                OutputFormat::Ndjson => write_ndjson_record(out, &change)?,
+                _ => change.render(out, args.show_command)?,
+            }
+            // New changes should show up right away
+            Ok(out.flush()?)
+        };
+        for change in read_changes(rotated, &filter, false) {
+            emit(change?)?;
+        }
+        return follow(path, &filter, emit);
     }
 
+    if args.transactions {
+        let transactions = TransactionReader::new(&sources, &filter).strict(args.strict);
+        let mut transactions =
+            report_corrupt(transactions, &corrupt).collect::<AnyResult<Vec<_>>>()?;
+        if let Some(n) = args.first {
+            transactions.truncate(n);
+        }
+        if let Some(n) = args.last {
+            transactions.drain(..transactions.len().saturating_sub(n));
+        }
+
+        match args.output {
+            OutputFormat::Text => {
+                for transaction in transactions {
+                    transaction.render(out)?;
+                }
+            }
+            format @ (OutputFormat::Csv | OutputFormat::Tsv) => {
+                let changes = transactions.into_iter().flat_map(|t| t.into_changes());
+                write_csv(out, changes.map(Ok), format, args.columns())?;
+            }
+            format => write_json(out, transactions.into_iter().map(Ok), format)?,
+        }
+        return check_corrupt(&corrupt);
+    }
+
+    let forward = report_corrupt(read_changes(&sources, &filter, args.strict), &corrupt);
+    let changes: Box<dyn Iterator<Item = AnyResult<PackageChange>>> = match (args.first, args.last)
+    {
+        (Some(n), _) => Box::new(forward.take(n)),
+        (_, Some(n)) if args.strict => {
+            // Corrupt lines are reported with their line number, which needs reading forward
+            let mut changes = VecDeque::with_capacity(n);
+            for change in forward {
+                changes.push_back(change?);
+                if changes.len() > n {
+                    changes.pop_front();
+                }
+            }
+            Box::new(changes.into_iter().map(Ok))
+        }
+        (_, Some(n)) => {
+            // Read backwards to avoid parsing the whole log, then restore chronological order
+            let mut changes = read_changes_rev(&sources, &filter)
+                .take(n)
+                .collect::<AnyResult<Vec<_>>>()?;
+            changes.reverse();
+            Box::new(changes.into_iter().map(Ok))
+        }
+        (None, None) => Box::new(forward),
+    };
+
+    match args.output {
+        OutputFormat::Text => {
+            for change in changes {
+                change?.render(out, args.show_command)?;
+            }
+        }
+        format @ (OutputFormat::Csv | OutputFormat::Tsv) => {
+            write_csv(out, changes, format, args.columns())?
+        }
+        format => write_json(out, changes, format)?,
+    }
+
+    check_corrupt(&corrupt)
+}
+
+fn log_sources(args: &Cli, conf: &PacmanConf) -> Vec<LogSource> {
+    let mut sources = if !args.log_files.is_empty() {
+        args.log_files
+            .iter()
+            .cloned()
+            .map(LogSource::from_path)
+            .collect()
+    } else {
+        vec![LogSource::piped_stdin().unwrap_or_else(|| LogSource::from_conf(conf))]
+    };
+    if !args.no_rotated {
+        sources = sources
+            .into_iter()
+            .flat_map(LogSource::with_rotated)
+            .collect();
+    }
+    sources
+}
+
+fn history(
+    args: &Cli,
+    sources: &[LogSource],
+    package: &str,
+    out: &mut impl WriteColor,
+    corrupt: &Cell<usize>,
+) -> AnyResult<()> {
+    // A single package: `*` is not expanded, its versions would mix with other packages'
+    let filter = ChangeFilter {
+        packages: vec![Regex::new(&format!("^{}$", regex::escape(package)))?],
+        ..Default::default()
+    };
+    let changes = report_corrupt(read_changes(sources, &filter, args.strict), corrupt)
+        .collect::<AnyResult<Vec<_>>>()?;
+    if changes.is_empty() {
+        bail!("No change found for `{package}`");
+    }
+
+    let history = PackageHistory::new(package, changes);
+    match args.output {
+        OutputFormat::Text => history.render(out, args.show_command),
+        OutputFormat::Csv | OutputFormat::Tsv => bail!("history does not support csv/tsv output"),
+        format => write_json_record(out, &history, format),
+    }
+}
+
+fn state(
+    args: &Cli,
+    sources: &[LogSource],
+    at: &DateTime<FixedOffset>,
+    names_only: bool,
+    out: &mut impl WriteColor,
+    corrupt: &Cell<usize>,
+) -> AnyResult<()> {
+    let filter = ChangeFilter {
+        until: Some(*at),
+        ..Default::default()
+    };
+    let changes = report_corrupt(read_changes(sources, &filter, args.strict), corrupt)
+        .collect::<AnyResult<Vec<_>>>()?;
+
+    let state = PackageState::replay(&changes);
+    match args.output {
+        OutputFormat::Text => state.render(out, names_only),
+        OutputFormat::Csv | OutputFormat::Tsv => bail!("state does not support csv/tsv output"),
+        format => write_json_record(out, &state, format),
+    }
+}
+
+fn diff(
+    args: &Cli,
+    sources: &[LogSource],
+    from: &LogPoint,
+    to: &LogPoint,
+    out: &mut impl WriteColor,
+    corrupt: &Cell<usize>,
+) -> AnyResult<()> {
+    let transactions = all_transactions(args, sources, corrupt)?;
+    check_transaction_point(&transactions, from)?;
+    check_transaction_point(&transactions, to)?;
+
+    let diff = StateDiff::new(
+        &PackageState::replay_until(&transactions, from),
+        &PackageState::replay_until(&transactions, to),
+    );
+    match args.output {
+        OutputFormat::Text => diff.render(out),
+        OutputFormat::Csv | OutputFormat::Tsv => bail!("diff does not support csv/tsv output"),
+        format => write_json_record(out, &diff, format),
+    }
+}
+
+fn rollback(
+    args: &Cli,
+    sources: &[LogSource],
+    to: &LogPoint,
+    conf: &PacmanConf,
+    out: &mut impl WriteColor,
+    corrupt: &Cell<usize>,
+) -> AnyResult<()> {
+    let transactions = all_transactions(args, sources, corrupt)?;
+    check_transaction_point(&transactions, to)?;
+
+    let current = PackageState::replay(transactions.iter().flat_map(Transaction::changes));
+    let diff = StateDiff::new(&current, &PackageState::replay_until(&transactions, to));
+    let plan = RollbackPlan::new(&diff, &PackageCache::from_conf(conf));
+    match args.output {
+        OutputFormat::Text => plan.render(out),
+        OutputFormat::Csv | OutputFormat::Tsv => bail!("rollback does not support csv/tsv output"),
+        format => write_json_record(out, &plan, format),
+    }
+}
+
+/// Every transaction of the log sources, unfiltered
+fn all_transactions(
+    args: &Cli,
+    sources: &[LogSource],
+    corrupt: &Cell<usize>,
+) -> AnyResult<Vec<Transaction>> {
+    let filter = ChangeFilter::default();
+    let transactions = TransactionReader::new(sources, &filter).strict(args.strict);
+    report_corrupt(transactions, corrupt).collect()
+}
+
+/// Make sure a transaction id refers to a transaction of the log
+fn check_transaction_point(transactions: &[Transaction], point: &LogPoint) -> AnyResult<()> {
+    if let LogPoint::Transaction(id) = *point {
+        let last = transactions.iter().filter_map(Transaction::id).max();
+        if id == 0 || last.is_none_or(|last| id > last) {
+            bail!("No {point} in the log");
+        }
+    }
     Ok(())
 }
+
+/// Report corrupt lines found in strict mode on stderr instead of stopping at the first one
+fn report_corrupt<'a, T: 'a>(
+    items: impl Iterator<Item = AnyResult<T>> + 'a,
+    corrupt: &'a Cell<usize>,
+) -> impl Iterator<Item = AnyResult<T>> + 'a {
+    items.filter(move |item| match item {
+        Err(e) if e.downcast_ref::<CorruptLine>().is_some() => {
+            eprintln!("error: {e:#}");
+            corrupt.set(corrupt.get() + 1);
+            false
+        }
+        _ => true,
+    })
+}
+
+/// Whether `error` comes from writing to a closed pipe
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        // Serializers wrap the io::Error rather than exposing it as their source
+        let kind = if let Some(e) = cause.downcast_ref::<serde_json::Error>() {
+            e.io_error_kind()
+        } else if let Some(csv::ErrorKind::Io(e)) =
+            cause.downcast_ref::<csv::Error>().map(csv::Error::kind)
+        {
+            Some(e.kind())
+        } else {
+            cause.downcast_ref::<io::Error>().map(io::Error::kind)
+        };
+        kind == Some(io::ErrorKind::BrokenPipe)
+    })
+}
+
+fn check_corrupt(corrupt: &Cell<usize>) -> AnyResult<()> {
+    match corrupt.get() {
+        0 => Ok(()),
+        1 => bail!("1 corrupt package change found"),
+        n => bail!("{n} corrupt package changes found"),
+    }
+}
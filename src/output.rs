@@ -0,0 +1,157 @@
+//! Machine-readable output
+//!
+//! Records follow the serialization schema documented on [`PackageChange`] and
+//! [`Transaction`](paclogrs::Transaction).
+//!
+//! `json` prints a single array of records, `ndjson` prints one record per line as soon as it is
+//! available.
+//!
+//! `csv` and `tsv` print one row per package change (transactions are flattened) with a header
+//! row naming the selected [`Column`]s, quoted as specified by RFC 4180. Missing values are
+//! empty fields.
+
+use std::{borrow::Borrow, io::Write};
+
+use anyhow::Result as AnyResult;
+use clap::ArgEnum;
+use serde::Serialize;
+
+use paclogrs::PackageChange;
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Ndjson,
+    Csv,
+    Tsv,
+}
+
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[clap(rename_all = "snake_case")]
+pub enum Column {
+    Name,
+    Timestamp,
+    RawTimestamp,
+    Action,
+    PreviousVersion,
+    CurrentVersion,
+    Command,
+    Line,
+}
+
+impl Column {
+    pub const DEFAULT: &'static [Self] = &[
+        Self::Timestamp,
+        Self::Action,
+        Self::Name,
+        Self::PreviousVersion,
+        Self::CurrentVersion,
+    ];
+
+    fn header(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Timestamp => "timestamp",
+            Self::RawTimestamp => "raw_timestamp",
+            Self::Action => "action",
+            Self::PreviousVersion => "previous_version",
+            Self::CurrentVersion => "current_version",
+            Self::Command => "command",
+            Self::Line => "line",
+        }
+    }
+
+    fn value(&self, change: &PackageChange) -> String {
+        match self {
+            Self::Name => change.name().to_string(),
+            Self::Timestamp => change.datetime().to_rfc3339(),
+            Self::RawTimestamp => change.raw_datetime().to_string(),
+            Self::Action => change.action().to_string(),
+            Self::PreviousVersion => change
+                .previous_version()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            Self::CurrentVersion => change
+                .current_version()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            Self::Command => change.command().unwrap_or_default().to_string(),
+            Self::Line => change.line().to_string(),
+        }
+    }
+}
+
+/// Serialize `records` as a JSON array, or as NDJSON streamed record by record
+pub fn write_json<W, T, I>(out: &mut W, records: I, format: OutputFormat) -> AnyResult<()>
+where
+    W: Write,
+    T: Serialize,
+    I: IntoIterator<Item = AnyResult<T>>,
+{
+    if format == OutputFormat::Ndjson {
+        for record in records {
+            write_ndjson_record(out, &record?)?;
+        }
+        return Ok(());
+    }
+
+    let records = records.into_iter().collect::<AnyResult<Vec<T>>>()?;
+    serde_json::to_writer_pretty(&mut *out, &records)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Serialize a single record, pretty-printed for `json`
+pub fn write_json_record<W: Write, T: Serialize>(
+    out: &mut W,
+    record: &T,
+    format: OutputFormat,
+) -> AnyResult<()> {
+    if format == OutputFormat::Ndjson {
+        return write_ndjson_record(out, record);
+    }
+
+    serde_json::to_writer_pretty(&mut *out, record)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Write a single NDJSON record, left to the caller to flush
+pub fn write_ndjson_record<W: Write, T: Serialize>(writer: &mut W, record: &T) -> AnyResult<()> {
+    serde_json::to_writer(&mut *writer, record)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Write `changes` as CSV or TSV with a header row
+pub fn write_csv<W, C, I>(
+    out: &mut W,
+    changes: I,
+    format: OutputFormat,
+    columns: &[Column],
+) -> AnyResult<()>
+where
+    W: Write,
+    C: Borrow<PackageChange>,
+    I: IntoIterator<Item = AnyResult<C>>,
+{
+    // RFC 4180 mandates CRLF line endings, TSV consumers expect plain LF
+    let (delimiter, terminator) = match format {
+        OutputFormat::Tsv => (b'\t', csv::Terminator::Any(b'\n')),
+        _ => (b',', csv::Terminator::CRLF),
+    };
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .terminator(terminator)
+        .from_writer(out);
+
+    writer.write_record(columns.iter().map(Column::header))?;
+    for change in changes {
+        let change = change?;
+        writer.write_record(columns.iter().map(|column| column.value(change.borrow())))?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
@@ -1,20 +1,32 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader, Write},
+    fmt::{self, Display},
+    io::{self, BufRead},
+    iter,
+    str::FromStr,
 };
 
-use anyhow::anyhow;
 use anyhow::Result as AnyResult;
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime};
 use lazy_static::lazy_static;
 use regex::Regex;
-use termcolor::{BufferedStandardStream, Color, ColorChoice, ColorSpec, WriteColor};
+use serde::Serialize;
+use termcolor::{Color, ColorSpec, WriteColor};
 
-#[derive(Debug)]
+use crate::{
+    error::{CorruptLine, LineError},
+    source::LogSource,
+    version::Version,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PacmanAction {
     Installed,
     Upgraded,
     Downgraded,
     Removed,
+    Reinstalled,
 }
 
 impl TryFrom<&str> for PacmanAction {
@@ -26,38 +38,117 @@ impl TryFrom<&str> for PacmanAction {
             "upgraded" => Ok(Self::Upgraded),
             "downgraded" => Ok(Self::Downgraded),
             "removed" => Ok(Self::Removed),
+            "reinstalled" => Ok(Self::Reinstalled),
             _ => Err(anyhow!("`{action}` is not a valid action!")),
         }
     }
 }
 
+impl Display for PacmanAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Installed => "installed",
+            Self::Upgraded => "upgraded",
+            Self::Downgraded => "downgraded",
+            Self::Removed => "removed",
+            Self::Reinstalled => "reinstalled",
+        })
+    }
+}
+
+impl FromStr for PacmanAction {
+    type Err = anyhow::Error;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        Self::try_from(action)
+    }
+}
+
 lazy_static! {
     static ref PACKAGE_CHANGE_REGEX: Regex = Regex::new(
         r"\[(?P<date>.*)\] \[ALPM\] (?P<action>[[[:alpha:]]]+) (?P<package>[a-z0-9@_+][a-z0-9@._+-]*) \((?P<version>.*)\)"
     )
     .unwrap();
 
-    static ref PACKAGE_VERSION_REGEX: Regex = Regex::new(
-        r"(^[a-z0-9.:+-]+)(?: -> ([a-z0-9.:+-]+$))?"
-    ).unwrap();
+    static ref PACMAN_COMMAND_REGEX: Regex = Regex::new(
+        r"^\[(?P<date>[^\]]*)\] \[PACMAN\] Running '(?P<command>.*)'$"
+    )
+    .unwrap();
+
+    static ref LOG_TAG_REGEX: Regex =
+        Regex::new(r"^\[[^\]]*\] \[(?P<tag>[^\]]+)\] (?P<message>.*)$").unwrap();
 }
 
-#[derive(Debug)]
-pub struct PackageChange {
-    name: String,
-    datetime: String,
-    action: PacmanAction,
-    previous_version: Option<String>,
-    current_version: Option<String>,
+/// Parse the bracketed timestamp of a log line
+///
+/// pacman used to log local time without timezone (`2018-05-01 10:22`) before switching to
+/// ISO-8601 (`2021-03-04T12:00:01+0100`).
+pub fn parse_datetime(raw: &str) -> AnyResult<DateTime<FixedOffset>> {
+    if let Ok(datetime) = DateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S%z") {
+        return Ok(datetime);
+    }
+
+    let naive = NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M")?;
+    naive
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|datetime| datetime.fixed_offset())
+        .ok_or(anyhow!("`{raw}` does not exist in the local timezone"))
 }
 
-impl PackageChange {
-    fn name_matches(name: &str, regexes: &[Regex]) -> bool {
-        if regexes.is_empty() {
+/// Command line of a `[PACMAN] Running '...'` log line
+pub fn parse_command(line: &str) -> Option<String> {
+    PACMAN_COMMAND_REGEX
+        .captures(line)
+        .map(|cap| String::from(&cap["command"]))
+}
+
+/// Whether `line` ends the scope of the last `[PACMAN] Running '...'` command
+///
+/// That is a transaction closing, or another front-end such as pamac logging (`[PAMAC] ...`):
+/// changes after it were not made by that command, e.g. after a `pacman -Sy` without
+/// transaction.
+pub(crate) fn ends_command(line: &str) -> bool {
+    let Some(cap) = LOG_TAG_REGEX.captures(line) else {
+        return false;
+    };
+    match &cap["tag"] {
+        "ALPM" => matches!(
+            &cap["message"],
+            "transaction completed" | "transaction failed" | "transaction interrupted"
+        ),
+        tag => tag != "PACMAN" && !tag.starts_with("ALPM"),
+    }
+}
+
+/// Turn a package name pattern where `*` matches anything into a [`Regex`] for
+/// [`ChangeFilter::packages`]
+pub fn package_glob(glob: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^{}$", regex::escape(glob).replace(r"\*", ".*")))
+}
+
+/// Criteria a log line must meet to be turned into a [`PackageChange`]
+#[derive(Debug, Default)]
+pub struct ChangeFilter {
+    pub packages: Vec<Regex>,
+    /// Actions to keep, all of them if empty
+    pub actions: Vec<PacmanAction>,
+    pub since: Option<DateTime<FixedOffset>>,
+    pub until: Option<DateTime<FixedOffset>>,
+    /// Substrings the invoking pacman command must contain one of, any command if empty
+    pub commands: Vec<String>,
+    /// Only keep changes whose action contradicts their versions, see
+    /// [`PackageChange::contradicts_versions`]
+    pub contradictions_only: bool,
+}
+
+impl ChangeFilter {
+    fn name_matches(&self, name: &str) -> bool {
+        if self.packages.is_empty() {
             return true;
         }
 
-        for regex in regexes {
+        for regex in &self.packages {
             if regex.is_match(name) {
                 return true;
             }
@@ -65,149 +156,496 @@ impl PackageChange {
         false
     }
 
-    pub fn from_line(line: String, regexes: &[Regex]) -> AnyResult<Self> {
-        if let Some(cap) = PACKAGE_CHANGE_REGEX.captures(&line) {
-            let name = String::from(
-                cap.name("package")
-                    .ok_or(anyhow!("No package name found"))?
-                    .as_str(),
-            );
-            if !Self::name_matches(&name, regexes) {
-                return Err(anyhow!(
-                    "Package `{name}` does not match one of the provided Regex"
-                ));
-            }
-
-            let action = PacmanAction::try_from(
-                cap.name("action")
-                    .ok_or(anyhow!("No PacmanAction found"))?
-                    .as_str(),
-            )?;
-            let datetime = String::from(cap.name("date").ok_or(anyhow!("No date found"))?.as_str());
-
-            if let Some(version_change) = PACKAGE_VERSION_REGEX.captures(
-                cap.name("version")
-                    .ok_or(anyhow!("No version change found"))?
-                    .as_str(),
-            ) {
-                let (mut previous_version, mut current_version) = (None, None);
-
-                let (lv, rv) = (
-                    version_change.get(1).map(|m| m.as_str().to_string()),
-                    version_change.get(2).map(|m| m.as_str().to_string()),
-                );
-                match action {
-                    PacmanAction::Installed => {
-                        current_version = Some(lv.ok_or(anyhow!("No current package version"))?);
-                    }
-                    PacmanAction::Upgraded | PacmanAction::Downgraded => {
-                        previous_version = Some(lv.ok_or(anyhow!("No previous package version"))?);
-                        current_version = Some(rv.ok_or(anyhow!("No current package version"))?);
-                    }
-                    PacmanAction::Removed => {
-                        previous_version = Some(lv.ok_or(anyhow!("No previous package version"))?);
-                    }
-                }
+    fn action_matches(&self, action: PacmanAction) -> bool {
+        self.actions.is_empty() || self.actions.contains(&action)
+    }
 
-                return Ok(PackageChange {
-                    name,
-                    datetime,
-                    action,
-                    previous_version,
-                    current_version,
-                });
-            }
+    pub fn command_matches(&self, command: Option<&str>) -> bool {
+        if self.commands.is_empty() {
+            return true;
         }
 
-        Err(anyhow!(
-            "Unable to create a new PackageChange from `{}`",
-            line
-        ))
+        command.is_some_and(|command| {
+            self.commands
+                .iter()
+                .any(|pattern| command.contains(pattern.as_str()))
+        })
+    }
+
+    fn datetime_matches(&self, datetime: &DateTime<FixedOffset>) -> bool {
+        self.since.is_none_or(|since| *datetime >= since)
+            && self.until.is_none_or(|until| *datetime <= until)
     }
 }
 
+/// Package-level event parsed from an `[ALPM]` log line
+///
+/// # Serialization schema
+///
+/// | Field              | Type             | Description                                          |
+/// |--------------------|------------------|------------------------------------------------------|
+/// | `name`             | string           | Package name                                         |
+/// | `timestamp`        | string           | RFC 3339 timestamp                                   |
+/// | `raw_timestamp`    | string           | Timestamp exactly as written in the log              |
+/// | `action`           | string           | `installed`, `upgraded`, `downgraded`, `removed` or `reinstalled` |
+/// | `previous_version` | string \| null   | Version before the change, `null` when installed     |
+/// | `current_version`  | string \| null   | Version after the change, `null` when removed        |
+/// | `command`          | string \| null   | pacman command line that made the change             |
+/// | `line`             | string           | Log line the change was parsed from                  |
+#[derive(Debug, Serialize)]
+pub struct PackageChange {
+    name: String,
+    #[serde(rename = "timestamp")]
+    datetime: DateTime<FixedOffset>,
+    #[serde(rename = "raw_timestamp")]
+    raw_datetime: String,
+    action: PacmanAction,
+    previous_version: Option<Version>,
+    current_version: Option<Version>,
+    /// pacman command line that triggered the change
+    command: Option<String>,
+    /// Log line the change was parsed from
+    line: String,
+}
+
 impl PackageChange {
-    pub fn print(&self) -> AnyResult<()> {
-        let color_choice = if atty::is(atty::Stream::Stdout) {
-            ColorChoice::Auto
-        } else {
-            ColorChoice::Never
+    pub fn from_line(line: String, filter: &ChangeFilter) -> Result<Self, LineError> {
+        let cap = PACKAGE_CHANGE_REGEX
+            .captures(&line)
+            .ok_or(LineError::NotPackageChange)?;
+
+        let name = String::from(&cap["package"]);
+        if !filter.name_matches(&name) {
+            return Err(LineError::Filtered);
+        }
+
+        let raw_datetime = String::from(&cap["date"]);
+        let datetime = parse_datetime(&raw_datetime)
+            .map_err(|e| LineError::Corrupt(format!("invalid timestamp `{raw_datetime}`: {e}")))?;
+        if !filter.datetime_matches(&datetime) {
+            return Err(LineError::Filtered);
+        }
+
+        let action = PacmanAction::try_from(&cap["action"])
+            .map_err(|e| LineError::Corrupt(e.to_string()))?;
+        if !filter.action_matches(action) {
+            return Err(LineError::Filtered);
+        }
+
+        let parse_version = |raw: &str| {
+            raw.parse::<Version>()
+                .map_err(|e| LineError::Corrupt(e.to_string()))
+        };
+        let (lv, rv) = match cap["version"].split_once(" -> ") {
+            Some((lv, rv)) => (Some(parse_version(lv)?), Some(parse_version(rv)?)),
+            None => (Some(parse_version(&cap["version"])?), None),
+        };
+        let missing = |what: &str| LineError::Corrupt(format!("no {what} package version"));
+
+        let (mut previous_version, mut current_version) = (None, None);
+        match action {
+            PacmanAction::Installed => {
+                current_version = Some(lv.ok_or_else(|| missing("current"))?);
+            }
+            PacmanAction::Upgraded | PacmanAction::Downgraded => {
+                previous_version = Some(lv.ok_or_else(|| missing("previous"))?);
+                current_version = Some(rv.ok_or_else(|| missing("current"))?);
+            }
+            PacmanAction::Removed => {
+                previous_version = Some(lv.ok_or_else(|| missing("previous"))?);
+            }
+            PacmanAction::Reinstalled => {
+                // Same version before and after
+                let version = lv.ok_or_else(|| missing("current"))?;
+                previous_version = Some(version.clone());
+                current_version = Some(version);
+            }
+        }
+
+        let change = PackageChange {
+            name,
+            datetime,
+            raw_datetime,
+            action,
+            previous_version,
+            current_version,
+            command: None,
+            line,
         };
+        if filter.contradictions_only && !change.contradicts_versions() {
+            return Err(LineError::Filtered);
+        }
+
+        Ok(change)
+    }
+}
+
+impl PackageChange {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn datetime(&self) -> &DateTime<FixedOffset> {
+        &self.datetime
+    }
+
+    pub fn action(&self) -> PacmanAction {
+        self.action
+    }
+
+    pub fn previous_version(&self) -> Option<&Version> {
+        self.previous_version.as_ref()
+    }
+
+    pub fn current_version(&self) -> Option<&Version> {
+        self.current_version.as_ref()
+    }
+
+    pub fn line(&self) -> &str {
+        &self.line
+    }
 
-        let mut stdout = BufferedStandardStream::stdout(color_choice);
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
 
-        stdout.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
-        stdout.write_all(format!("[{}]", self.datetime).as_bytes())?;
+    pub fn with_command(self, command: Option<String>) -> Self {
+        Self { command, ..self }
+    }
+
+    /// Timestamp exactly as written in the log
+    pub fn raw_datetime(&self) -> &str {
+        &self.raw_datetime
+    }
+
+    /// Whether the version ordering disagrees with the action, such as an upgrade to an older
+    /// or the same version according to `vercmp`
+    pub fn contradicts_versions(&self) -> bool {
+        match (self.action, &self.previous_version, &self.current_version) {
+            (PacmanAction::Upgraded, Some(previous), Some(current)) => current <= previous,
+            (PacmanAction::Downgraded, Some(previous), Some(current)) => current >= previous,
+            _ => false,
+        }
+    }
+}
+
+impl PackageChange {
+    /// Write the change as a colored line to `out`, a terminal stream or an in-memory
+    /// [`termcolor::Buffer`]
+    ///
+    /// Nothing is flushed, callers share a single buffered stream across records.
+    pub fn render<W: WriteColor>(&self, out: &mut W, show_command: bool) -> AnyResult<()> {
+        self.render_with_prefix(out, "", show_command)
+    }
+
+    /// Same as [`PackageChange::render`] with `prefix` written before the line
+    pub fn render_with_prefix<W: WriteColor>(
+        &self,
+        out: &mut W,
+        prefix: &str,
+        show_command: bool,
+    ) -> AnyResult<()> {
+        out.write_all(prefix.as_bytes())?;
+
+        out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+        out.write_all(format!("[{}]", self.raw_datetime).as_bytes())?;
 
         match self.action {
             PacmanAction::Installed => {
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
-                stdout.write_all(b" installed ")?;
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+                out.write_all(b" installed ")?;
             }
             PacmanAction::Upgraded => {
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
-                stdout.write_all(b" upgraded ")?;
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+                out.write_all(b" upgraded ")?;
             }
             PacmanAction::Downgraded => {
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Magenta)))?;
-                stdout.write_all(b" downgraded ")?;
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Magenta)))?;
+                out.write_all(b" downgraded ")?;
             }
             PacmanAction::Removed => {
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
-                stdout.write_all(b" removed ")?;
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
+                out.write_all(b" removed ")?;
+            }
+            PacmanAction::Reinstalled => {
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Blue)))?;
+                out.write_all(b" reinstalled ")?;
             }
         }
 
-        stdout.set_color(
+        out.set_color(
             ColorSpec::new()
                 .set_fg(Some(Color::Yellow))
                 .set_bold(true)
                 .set_intense(true),
         )?;
-        stdout.write_all(self.name.as_bytes())?;
+        out.write_all(self.name.as_bytes())?;
 
-        stdout.reset()?;
-        stdout.write_all(b" (")?;
+        out.reset()?;
+        out.write_all(b" (")?;
 
         match self.action {
-            PacmanAction::Installed => {
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
-                stdout.write_all(self.current_version.as_ref().unwrap().as_bytes())?;
+            PacmanAction::Installed | PacmanAction::Reinstalled => {
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+                out.write_all(self.current_version.as_ref().unwrap().as_str().as_bytes())?;
             }
             PacmanAction::Upgraded | PacmanAction::Downgraded => {
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Magenta)))?;
-                stdout.write_all(self.previous_version.as_ref().unwrap().as_bytes())?;
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Magenta)))?;
+                out.write_all(self.previous_version.as_ref().unwrap().as_str().as_bytes())?;
 
-                stdout.reset()?;
-                stdout.write_all(b" -> ")?;
+                out.reset()?;
+                out.write_all(b" -> ")?;
 
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
-                stdout.write_all(self.current_version.as_ref().unwrap().as_bytes())?;
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+                out.write_all(self.current_version.as_ref().unwrap().as_str().as_bytes())?;
             }
             PacmanAction::Removed => {
-                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Magenta)))?;
-                stdout.write_all(self.previous_version.as_ref().unwrap().as_bytes())?;
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Magenta)))?;
+                out.write_all(self.previous_version.as_ref().unwrap().as_str().as_bytes())?;
             }
         }
 
-        stdout.reset()?;
-        stdout.write_all(b")\n")?;
+        out.reset()?;
+        out.write_all(b")")?;
+
+        if self.contradicts_versions() {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))?;
+            out.write_all(format!(" [not {} according to vercmp]", self.action).as_bytes())?;
+            out.reset()?;
+        }
+
+        if let (true, Some(command)) = (show_command, self.command()) {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            out.write_all(format!(" via '{command}'").as_bytes())?;
+            out.reset()?;
+        }
+        out.write_all(b"\n")?;
 
         Ok(())
     }
 }
 
-const PACMAN_LOG_FILE: &str = "/var/log/pacman.log";
+/// Lines of a log with invalid UTF-8 replaced rather than treated as an error
+pub(crate) struct LogLines<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    /// Number of the last line read, 1-based
+    pub line_number: usize,
+}
+
+impl<R: BufRead> LogLines<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            line_number: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for LogLines<R> {
+    type Item = io::Result<String>;
 
-pub fn get_changes(regexes: Vec<Regex>) -> AnyResult<Vec<PackageChange>> {
-    let file_bufreader = BufReader::new(File::open(PACMAN_LOG_FILE)?);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.buffer.clear();
+        match self.reader.read_until(b'\n', &mut self.buffer) {
+            Ok(0) => None,
+            Ok(_) => {
+                self.line_number += 1;
+                let line = String::from_utf8_lossy(&self.buffer);
+                Some(Ok(line.trim_end_matches(['\r', '\n']).to_string()))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Streams the [`PackageChange`]s of a log matching a [`ChangeFilter`]
+///
+/// Lines that are not package changes or that do not match the filter are skipped, I/O errors
+/// are yielded. Corrupt package changes are skipped too, unless in [strict] mode where they are
+/// yielded as [`CorruptLine`] errors and reading goes on.
+///
+/// [strict]: ChangeReader::strict
+pub struct ChangeReader<'a, R> {
+    lines: LogLines<R>,
+    filter: &'a ChangeFilter,
+    strict: bool,
+    /// Last `[PACMAN] Running '...'` command line seen
+    command: Option<String>,
+}
 
-    let mut changes = Vec::new();
-    for line in file_bufreader.lines().filter_map(|r| r.ok()) {
-        if let Ok(change) = PackageChange::from_line(line, &regexes) {
-            changes.push(change);
+impl<'a, R: BufRead> ChangeReader<'a, R> {
+    pub fn new(reader: R, filter: &'a ChangeFilter) -> Self {
+        Self {
+            lines: LogLines::new(reader),
+            filter,
+            strict: false,
+            command: None,
         }
     }
 
-    Ok(changes)
+    pub fn strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+}
+
+impl<R: BufRead> Iterator for ChangeReader<'_, R> {
+    type Item = AnyResult<PackageChange>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+
+            if let Some(running) = parse_command(&line) {
+                self.command = Some(running);
+                continue;
+            }
+            if ends_command(&line) {
+                self.command = None;
+                continue;
+            }
+            if !self.filter.command_matches(self.command.as_deref()) {
+                continue;
+            }
+
+            match PackageChange::from_line(line.clone(), self.filter) {
+                Ok(change) => return Some(Ok(change.with_command(self.command.clone()))),
+                Err(LineError::Corrupt(reason)) if self.strict => {
+                    return Some(Err(CorruptLine {
+                        line_number: self.lines.line_number,
+                        line,
+                        reason,
+                    }
+                    .into()))
+                }
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+/// Stream the changes of every source one after the other, opening them as needed
+///
+/// Errors are given the source they come from as context.
+pub fn read_changes<'a>(
+    sources: &'a [LogSource],
+    filter: &'a ChangeFilter,
+    strict: bool,
+) -> impl Iterator<Item = AnyResult<PackageChange>> + 'a {
+    sources.iter().flat_map(move |source| {
+        let changes: Box<dyn Iterator<Item = AnyResult<PackageChange>>> = match source.open() {
+            Ok(reader) => Box::new(
+                ChangeReader::new(reader, filter)
+                    .strict(strict)
+                    .map(move |change| change.with_context(|| source.to_string())),
+            ),
+            Err(e) => Box::new(iter::once(Err(e))),
+        };
+        changes
+    })
+}
+
+/// Collect the changes of every source, see [`read_changes`] to stream them instead
+pub fn get_changes(sources: &[LogSource], filter: &ChangeFilter) -> AnyResult<Vec<PackageChange>> {
+    read_changes(sources, filter, false).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use termcolor::Buffer;
+
+    use super::*;
+
+    /// A `pacman -Sy` without transaction followed by a pamac upgrade, then a pacman
+    /// transaction followed by one from a front-end that logs nothing
+    const FOREIGN_LOG: &str = "\
+[2021-03-04T12:00:00+0100] [PACMAN] Running 'pacman -Sy'
+[2021-03-04T12:00:00+0100] [PACMAN] synchronizing package lists
+[2021-03-04T12:05:00+0100] [PAMAC] synchronizing package lists
+[2021-03-04T12:05:01+0100] [ALPM] transaction started
+[2021-03-04T12:05:01+0100] [ALPM] upgraded foo (1.0-1 -> 1.1-1)
+[2021-03-04T12:05:02+0100] [ALPM] transaction completed
+[2021-03-05T09:00:00+0100] [PACMAN] Running 'pacman -S bar'
+[2021-03-05T09:00:00+0100] [ALPM] transaction started
+[2021-03-05T09:00:00+0100] [ALPM] installed bar (1.0-1)
+[2021-03-05T09:00:01+0100] [ALPM] transaction completed
+[2021-03-06T09:00:00+0100] [ALPM] transaction started
+[2021-03-06T09:00:00+0100] [ALPM] installed baz (1.0-1)
+[2021-03-06T09:00:01+0100] [ALPM] transaction completed
+";
+
+    fn change(line: &str) -> PackageChange {
+        PackageChange::from_line(line.to_string(), &ChangeFilter::default()).unwrap()
+    }
+
+    #[test]
+    fn render_without_color() {
+        let mut out = Buffer::no_color();
+        change("[2021-03-04T12:00:01+0100] [ALPM] upgraded linux (5.11.1-1 -> 5.11.2-1)")
+            .render(&mut out, false)
+            .unwrap();
+        change("[2018-05-01 10:22] [ALPM] removed foo (1.0-1)")
+            .with_command(Some(String::from("pacman -Rs foo")))
+            .render_with_prefix(&mut out, "    ", true)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out.into_inner()).unwrap(),
+            "[2021-03-04T12:00:01+0100] upgraded linux (5.11.1-1 -> 5.11.2-1)\n    \
+             [2018-05-01 10:22] removed foo (1.0-1) via 'pacman -Rs foo'\n"
+        );
+    }
+
+    #[test]
+    fn render_contradiction_marker() {
+        let mut out = Buffer::no_color();
+        change("[2021-03-04T12:00:01+0100] [ALPM] upgraded foo (2.0-1 -> 1.0-1)")
+            .render(&mut out, false)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out.into_inner()).unwrap(),
+            "[2021-03-04T12:00:01+0100] upgraded foo (2.0-1 -> 1.0-1) \
+             [not upgraded according to vercmp]\n"
+        );
+    }
+
+    #[test]
+    fn command_does_not_outlive_its_scope() {
+        let filter = ChangeFilter::default();
+        let changes: Vec<(String, Option<String>)> =
+            ChangeReader::new(Cursor::new(FOREIGN_LOG), &filter)
+                .map(|change| {
+                    let change = change.unwrap();
+                    (
+                        change.name().to_string(),
+                        change.command().map(String::from),
+                    )
+                })
+                .collect();
+
+        assert_eq!(
+            changes,
+            [
+                (String::from("foo"), None),
+                (String::from("bar"), Some(String::from("pacman -S bar"))),
+                (String::from("baz"), None),
+            ]
+        );
+
+        let filter = ChangeFilter {
+            commands: vec![String::from("-Sy")],
+            ..Default::default()
+        };
+        assert_eq!(
+            ChangeReader::new(Cursor::new(FOREIGN_LOG), &filter).count(),
+            0
+        );
+    }
 }
@@ -0,0 +1,66 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result as AnyResult;
+
+pub const PACMAN_CONF_FILE: &str = "/etc/pacman.conf";
+
+/// Subset of the `[options]` section of pacman.conf that paclogrs cares about
+#[derive(Debug, Default)]
+pub struct PacmanConf {
+    pub log_file: Option<PathBuf>,
+    /// Every `CacheDir`, in order
+    pub cache_dirs: Vec<PathBuf>,
+    /// Whether `Color` is enabled, `None` if pacman.conf could not be read
+    pub color: Option<bool>,
+}
+
+impl PacmanConf {
+    pub fn load() -> Self {
+        Self::from_path(PACMAN_CONF_FILE).unwrap_or_default()
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> AnyResult<Self> {
+        let reader = BufReader::new(File::open(path)?);
+
+        let mut conf = Self {
+            color: Some(false),
+            ..Default::default()
+        };
+        let mut in_options = false;
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                in_options = &line[1..line.len() - 1] == "options";
+                continue;
+            }
+            if !in_options {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), Some(value.trim())),
+                None => (line, None),
+            };
+            match (key, value) {
+                ("LogFile", Some(value)) => conf.log_file = Some(PathBuf::from(value)),
+                // Can be repeated and hold several space-separated directories
+                ("CacheDir", Some(value)) => conf
+                    .cache_dirs
+                    .extend(value.split_whitespace().map(PathBuf::from)),
+                ("Color", _) => conf.color = Some(true),
+                _ => {}
+            }
+        }
+
+        Ok(conf)
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Seek, SeekFrom},
+    iter,
+};
+
+use anyhow::Result as AnyResult;
+
+use crate::{
+    paclog::{ends_command, parse_command, read_changes, ChangeFilter, PackageChange},
+    source::LogSource,
+};
+
+const CHUNK_SIZE: u64 = 8192;
+
+/// Lines of a file from the last one to the first one
+struct ReverseLines<R> {
+    reader: R,
+    /// Offset of the start of what has been read so far
+    position: Option<u64>,
+    /// Read bytes not yet returned as lines
+    partial: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read + Seek> ReverseLines<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            position: None,
+            partial: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Prepend the chunk preceding what has been read so far to `partial`
+    fn read_chunk(&mut self) -> io::Result<()> {
+        let end = match self.position {
+            Some(position) => position,
+            None => self.reader.seek(SeekFrom::End(0))?,
+        };
+        // An empty file has no line, not a single empty one
+        if end == 0 {
+            self.done = true;
+        }
+        let start = end.saturating_sub(CHUNK_SIZE);
+
+        let mut chunk = vec![0; (end - start) as usize];
+        self.reader.seek(SeekFrom::Start(start))?;
+        self.reader.read_exact(&mut chunk)?;
+
+        // The last line of the file is terminated, not followed by an empty line
+        if self.position.is_none() && chunk.last() == Some(&b'\n') {
+            chunk.pop();
+        }
+
+        chunk.append(&mut self.partial);
+        self.partial = chunk;
+        self.position = Some(start);
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Iterator for ReverseLines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done {
+                return None;
+            }
+
+            if let Some(newline) = self.partial.iter().rposition(|&b| b == b'\n') {
+                let line = String::from_utf8_lossy(&self.partial[newline + 1..]).into_owned();
+                self.partial.truncate(newline);
+                return Some(Ok(line.trim_end_matches('\r').to_string()));
+            }
+
+            if self.position == Some(0) {
+                self.done = true;
+                let line = String::from_utf8_lossy(&self.partial).into_owned();
+                return Some(Ok(line.trim_end_matches('\r').to_string()));
+            }
+
+            if let Err(e) = self.read_chunk() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// Streams the [`PackageChange`]s of a log matching a [`ChangeFilter`] from the most recent one
+///
+/// Only reads as much of the end of the file as needed. Changes are held back until the
+/// `[PACMAN] Running '...'` line preceding them is reached to know which command made them, or
+/// until a line ending the scope of any command (a closing transaction marker, another
+/// front-end's line) shows they were made by none.
+pub struct ReverseChangeReader<'a, R> {
+    lines: ReverseLines<R>,
+    filter: &'a ChangeFilter,
+    /// Changes waiting for their command line, most recent first
+    pending: Vec<PackageChange>,
+    ready: VecDeque<PackageChange>,
+    done: bool,
+}
+
+impl<'a, R: Read + Seek> ReverseChangeReader<'a, R> {
+    pub fn new(reader: R, filter: &'a ChangeFilter) -> Self {
+        Self {
+            lines: ReverseLines::new(reader),
+            filter,
+            pending: Vec::new(),
+            ready: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    fn release_pending(&mut self, command: Option<String>) {
+        if !self.filter.command_matches(command.as_deref()) {
+            self.pending.clear();
+            return;
+        }
+        for change in self.pending.drain(..) {
+            self.ready.push_back(change.with_command(command.clone()));
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for ReverseChangeReader<'_, R> {
+    type Item = AnyResult<PackageChange>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(change) = self.ready.pop_front() {
+                return Some(Ok(change));
+            }
+            if self.done {
+                return None;
+            }
+
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    if let Some(command) = parse_command(&line) {
+                        self.release_pending(Some(command));
+                    } else if ends_command(&line) {
+                        // No `Running` line between this one and the pending changes
+                        self.release_pending(None);
+                    } else if let Ok(change) = PackageChange::from_line(line, self.filter) {
+                        self.pending.push(change);
+                    }
+                }
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+                None => {
+                    self.done = true;
+                    self.release_pending(None);
+                }
+            }
+        }
+    }
+}
+
+/// Same as [`read_changes`] from the most recent change to the oldest one
+///
+/// Plain files are read backwards, compressed files and stdin are read in full first.
+pub fn read_changes_rev<'a>(
+    sources: &'a [LogSource],
+    filter: &'a ChangeFilter,
+) -> impl Iterator<Item = AnyResult<PackageChange>> + 'a {
+    sources.iter().rev().flat_map(move |source| {
+        let changes: Box<dyn Iterator<Item = AnyResult<PackageChange>>> =
+            match source.open_seekable() {
+                Ok(Some(file)) => Box::new(ReverseChangeReader::new(file, filter)),
+                Ok(None) => {
+                    let changes: Vec<_> =
+                        read_changes(std::slice::from_ref(source), filter, false).collect();
+                    Box::new(changes.into_iter().rev())
+                }
+                Err(e) => Box::new(iter::once(Err(e))),
+            };
+        changes
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::paclog::{ChangeReader, LogLines};
+
+    fn reverse_lines(log: &[u8]) -> Vec<String> {
+        ReverseLines::new(Cursor::new(log))
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    /// Lines as read forward, last one first
+    fn forward_lines(log: &[u8]) -> Vec<String> {
+        let mut lines: Vec<String> = LogLines::new(Cursor::new(log))
+            .collect::<io::Result<_>>()
+            .unwrap();
+        lines.reverse();
+        lines
+    }
+
+    fn reverse_changes(log: &[u8], filter: &ChangeFilter) -> Vec<serde_json::Value> {
+        ReverseChangeReader::new(Cursor::new(log), filter)
+            .map(|change| serde_json::to_value(change.unwrap()).unwrap())
+            .collect()
+    }
+
+    /// Changes as read forward, most recent first
+    fn forward_changes(log: &[u8], filter: &ChangeFilter) -> Vec<serde_json::Value> {
+        let mut changes: Vec<_> = ChangeReader::new(Cursor::new(log), filter)
+            .map(|change| serde_json::to_value(change.unwrap()).unwrap())
+            .collect();
+        changes.reverse();
+        changes
+    }
+
+    /// Transactions whose lines add up to several chunks, one line longer than a chunk
+    fn long_log() -> String {
+        let mut log = String::new();
+        for i in 0..200 {
+            log += &format!(
+                "[2021-03-04T12:{:02}:00+0100] [PACMAN] Running 'pacman -S pkg{i}'\n",
+                i % 60
+            );
+            log += "[2021-03-04T12:00:00+0100] [ALPM] transaction started\n";
+            log += &format!("[2021-03-04T12:00:00+0100] [ALPM] installed pkg{i} (1.0-{i})\n");
+            log += "[2021-03-04T12:00:00+0100] [ALPM] transaction completed\n";
+        }
+        log += &format!(
+            "[2021-03-05T09:00:00+0100] [ALPM-SCRIPTLET] {}\n",
+            "x".repeat(20_000)
+        );
+        log += "[2021-03-05T09:00:00+0100] [ALPM] upgraded pkg0 (1.0-0 -> 1.1-0)\n";
+        log
+    }
+
+    /// A `pacman -Sy` without transaction followed by a pamac upgrade, then a pacman
+    /// transaction followed by one from a front-end that logs nothing
+    const FOREIGN_LOG: &str = "\
+[2021-03-04T12:00:00+0100] [PACMAN] Running 'pacman -Sy'
+[2021-03-04T12:00:00+0100] [PACMAN] synchronizing package lists
+[2021-03-04T12:05:00+0100] [PAMAC] synchronizing package lists
+[2021-03-04T12:05:01+0100] [ALPM] transaction started
+[2021-03-04T12:05:01+0100] [ALPM] upgraded foo (1.0-1 -> 1.1-1)
+[2021-03-04T12:05:02+0100] [ALPM] transaction completed
+[2021-03-05T09:00:00+0100] [PACMAN] Running 'pacman -S bar'
+[2021-03-05T09:00:00+0100] [ALPM] transaction started
+[2021-03-05T09:00:00+0100] [ALPM] installed bar (1.0-1)
+[2021-03-05T09:00:01+0100] [ALPM] transaction completed
+[2021-03-06T09:00:00+0100] [ALPM] transaction started
+[2021-03-06T09:00:00+0100] [ALPM] installed baz (1.0-1)
+[2021-03-06T09:00:01+0100] [ALPM] transaction completed
+";
+
+    #[test]
+    fn command_does_not_outlive_its_scope() {
+        let filter = ChangeFilter::default();
+        let changes: Vec<(String, Option<String>)> =
+            ReverseChangeReader::new(Cursor::new(FOREIGN_LOG), &filter)
+                .map(|change| {
+                    let change = change.unwrap();
+                    (
+                        change.name().to_string(),
+                        change.command().map(String::from),
+                    )
+                })
+                .collect();
+
+        assert_eq!(
+            changes,
+            [
+                (String::from("baz"), None),
+                (String::from("bar"), Some(String::from("pacman -S bar"))),
+                (String::from("foo"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_match_forward_reading() {
+        let log = long_log();
+        assert!(log.len() as u64 > 3 * CHUNK_SIZE);
+        for log in [
+            log.clone(),
+            // Missing trailing newline
+            log.trim_end().to_string(),
+            log.replace('\n', "\r\n"),
+            String::from("\n"),
+            String::from("\nfoo\n\nbar"),
+        ] {
+            assert_eq!(reverse_lines(log.as_bytes()), forward_lines(log.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn line_longer_than_a_chunk() {
+        let long = "y".repeat(3 * CHUNK_SIZE as usize + 1);
+        let log = format!("first\n{long}\nlast\n");
+        assert_eq!(
+            reverse_lines(log.as_bytes()),
+            ["last", long.as_str(), "first"]
+        );
+    }
+
+    #[test]
+    fn empty_file() {
+        assert!(reverse_lines(b"").is_empty());
+        assert!(reverse_changes(b"", &ChangeFilter::default()).is_empty());
+    }
+
+    #[test]
+    fn changes_match_forward_reading() {
+        let log = long_log();
+        let filters = [
+            ChangeFilter::default(),
+            ChangeFilter {
+                commands: vec![String::from("pkg1")],
+                ..Default::default()
+            },
+        ];
+        for log in [
+            log.clone(),
+            log.trim_end().to_string(),
+            log.replace('\n', "\r\n"),
+        ] {
+            for filter in &filters {
+                let changes = reverse_changes(log.as_bytes(), filter);
+                assert!(!changes.is_empty());
+                assert_eq!(changes, forward_changes(log.as_bytes(), filter));
+            }
+        }
+    }
+
+    #[test]
+    fn changes_wait_for_their_command() {
+        // The Running line ends up chunks before the changes it made
+        let mut log = String::from("[2021-03-04T12:00:00+0100] [PACMAN] Running 'pacman -Syu'\n");
+        log += "[2021-03-04T12:00:00+0100] [ALPM] transaction started\n";
+        for i in 0..300 {
+            log += &format!("[2021-03-04T12:00:00+0100] [ALPM] upgraded pkg{i} (1.0-1 -> 1.1-1)\n");
+        }
+        assert!(log.len() as u64 > 2 * CHUNK_SIZE);
+
+        let filter = ChangeFilter::default();
+        let changes = reverse_changes(log.as_bytes(), &filter);
+        assert_eq!(changes.len(), 300);
+        assert!(changes
+            .iter()
+            .all(|change| change["command"] == "pacman -Syu"));
+        assert_eq!(changes, forward_changes(log.as_bytes(), &filter));
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result as AnyResult;
+use serde::Serialize;
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::{diff::StateDiff, pacman_conf::PacmanConf, version::Version};
+
+pub const PACMAN_CACHE_DIR: &str = "/var/cache/pacman/pkg";
+
+/// Package files found in pacman's cache directories
+#[derive(Debug, Default)]
+pub struct PackageCache {
+    files: Vec<PathBuf>,
+}
+
+impl PackageCache {
+    /// List the files of `dirs`, skipping directories that cannot be read
+    pub fn new<P: AsRef<Path>>(dirs: &[P]) -> Self {
+        let files = dirs
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        Self { files }
+    }
+
+    /// `CacheDir`s from pacman.conf, falling back to [`PACMAN_CACHE_DIR`]
+    pub fn from_conf(conf: &PacmanConf) -> Self {
+        if conf.cache_dirs.is_empty() {
+            Self::new(&[PACMAN_CACHE_DIR])
+        } else {
+            Self::new(&conf.cache_dirs)
+        }
+    }
+
+    /// Package file of `name` at `version` for any architecture
+    /// (`name-version-arch.pkg.tar.*`, signatures excluded)
+    pub fn find(&self, name: &str, version: &Version) -> Option<&Path> {
+        let prefix = format!("{name}-{version}-");
+        self.files
+            .iter()
+            .find(|path| {
+                let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                    return false;
+                };
+                file_name
+                    .strip_prefix(&prefix)
+                    .and_then(|rest| rest.split_once(".pkg.tar"))
+                    .is_some_and(|(arch, extension)| {
+                        !arch.is_empty() && !arch.contains('-') && !extension.ends_with(".sig")
+                    })
+            })
+            .map(PathBuf::as_path)
+    }
+}
+
+/// Package to reinstall at the version it had at the rollback point
+#[derive(Debug, Serialize)]
+pub struct RollbackTarget {
+    name: String,
+    /// `None` if the package is not installed anymore
+    current_version: Option<Version>,
+    target_version: Version,
+    /// `None` if missing from the cache
+    file: Option<PathBuf>,
+}
+
+impl RollbackTarget {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn current_version(&self) -> Option<&Version> {
+        self.current_version.as_ref()
+    }
+
+    pub fn target_version(&self) -> &Version {
+        &self.target_version
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+}
+
+/// What to reinstall and remove to get back to an earlier package state
+///
+/// # Serialization schema
+///
+/// | Field     | Type  | Description                                                        |
+/// |-----------|-------|--------------------------------------------------------------------|
+/// | `install` | array | Packages to reinstall at their former version                      |
+/// | `remove`  | array | Names of the packages installed since the rollback point           |
+///
+/// An install entry has a `name`, a `current_version` (`null` if not installed anymore), a
+/// `target_version` and the cached package `file` (`null` if missing from the cache).
+#[derive(Debug, Serialize)]
+pub struct RollbackPlan {
+    install: Vec<RollbackTarget>,
+    remove: Vec<String>,
+}
+
+impl RollbackPlan {
+    /// Plan going from the current state to the target one, as compared by `diff`
+    pub fn new(diff: &StateDiff, cache: &PackageCache) -> Self {
+        let install = [diff.added(), diff.upgraded(), diff.downgraded()]
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let target_version = entry.to()?.clone();
+                Some(RollbackTarget {
+                    name: entry.name().to_string(),
+                    current_version: entry.from().cloned(),
+                    file: cache
+                        .find(entry.name(), &target_version)
+                        .map(Path::to_path_buf),
+                    target_version,
+                })
+            })
+            .collect();
+        let remove = diff
+            .removed()
+            .iter()
+            .map(|entry| entry.name().to_string())
+            .collect();
+
+        Self { install, remove }
+    }
+
+    pub fn install(&self) -> &[RollbackTarget] {
+        &self.install
+    }
+
+    pub fn remove(&self) -> &[String] {
+        &self.remove
+    }
+
+    /// Targets without a cached package file
+    pub fn missing(&self) -> impl Iterator<Item = &RollbackTarget> {
+        self.install.iter().filter(|target| target.file.is_none())
+    }
+
+    /// `pacman -U` command reinstalling the cached files, `None` if there is none
+    pub fn install_command(&self) -> Option<String> {
+        let files: Vec<String> = self
+            .install
+            .iter()
+            .filter_map(|target| target.file())
+            .map(|file| shell_quote(&file.to_string_lossy()))
+            .collect();
+        (!files.is_empty()).then(|| format!("pacman -U {}", files.join(" ")))
+    }
+
+    /// `pacman -R` command removing the packages installed since, `None` if there is none
+    pub fn remove_command(&self) -> Option<String> {
+        (!self.remove.is_empty()).then(|| format!("pacman -R {}", self.remove.join(" ")))
+    }
+}
+
+/// Quote `word` for a POSIX shell if it contains anything but safe characters
+fn shell_quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "/._-+:@%=,".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+impl RollbackPlan {
+    pub fn render<W: WriteColor>(&self, out: &mut W) -> AnyResult<()> {
+        if self.install.is_empty() && self.remove.is_empty() {
+            out.write_all(b"Nothing to roll back\n")?;
+            return Ok(());
+        }
+
+        let width = self
+            .install
+            .iter()
+            .map(RollbackTarget::name)
+            .chain(self.remove.iter().map(String::as_str))
+            .map(str::len)
+            .max()
+            .unwrap_or_default();
+        fn current_version(target: &RollbackTarget) -> &str {
+            target.current_version().map_or("none", Version::as_str)
+        }
+        let versions_width = self
+            .install
+            .iter()
+            .map(|target| current_version(target).len() + target.target_version().as_str().len())
+            .max()
+            .unwrap_or_default();
+        for target in self.install() {
+            out.set_color(
+                ColorSpec::new()
+                    .set_fg(Some(Color::Yellow))
+                    .set_bold(true)
+                    .set_intense(true),
+            )?;
+            out.write_all(format!("{:width$}", target.name()).as_bytes())?;
+            out.reset()?;
+            out.write_all(b"  ")?;
+
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Magenta)))?;
+            let current = current_version(target);
+            out.write_all(current.as_bytes())?;
+            out.reset()?;
+            out.write_all(b" -> ")?;
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+            let padding = versions_width - current.len();
+            out.write_all(format!("{:padding$}", target.target_version().as_str()).as_bytes())?;
+
+            match target.file() {
+                Some(file) => {
+                    out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+                    out.write_all(format!("  {}", file.display()).as_bytes())?;
+                }
+                None => {
+                    out.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
+                    out.write_all(b"  missing from the cache")?;
+                }
+            }
+            out.reset()?;
+            out.write_all(b"\n")?;
+        }
+        for name in self.remove() {
+            out.set_color(
+                ColorSpec::new()
+                    .set_fg(Some(Color::Yellow))
+                    .set_bold(true)
+                    .set_intense(true),
+            )?;
+            out.write_all(format!("{name:width$}").as_bytes())?;
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
+            out.write_all(b"  to remove")?;
+            out.reset()?;
+            out.write_all(b"\n")?;
+        }
+
+        let missing = match self.missing().count() {
+            0 => None,
+            1 => Some(String::from("1 package file is missing from the cache")),
+            n => Some(format!("{n} package files are missing from the cache")),
+        };
+        if let Some(missing) = missing {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))?;
+            out.write_all(format!("\n{missing}\n").as_bytes())?;
+            out.reset()?;
+        }
+
+        out.write_all(b"\n")?;
+        for command in [self.install_command(), self.remove_command()]
+            .into_iter()
+            .flatten()
+        {
+            out.set_color(ColorSpec::new().set_bold(true))?;
+            out.write_all(command.as_bytes())?;
+            out.reset()?;
+            out.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_package_files() {
+        let cache = PackageCache {
+            files: [
+                "/cache/foo-1.0-1.1-x86_64.pkg.tar.zst",
+                "/cache/foo-1.0-1-x86_64.pkg.tar.zst.sig",
+                "/cache/foo-1.0-1-x86_64.pkg.tar.zst",
+                "/cache/foo-bar-1.0-1-any.pkg.tar.xz",
+                "/cache/baz-2:3.1-2-x86_64.pkg.tar.zst",
+                "/cache/qux-1.0-1-x86-64.pkg.tar.zst",
+                "/cache/qux-1.0-1-.pkg.tar.zst",
+                "/cache/quux-1.0-1-any.pkg.tar.zst.sig",
+            ]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect(),
+        };
+
+        let cases = [
+            // the signature and the longer 1.0-1.1 version are skipped
+            ("foo", "1.0-1", Some("/cache/foo-1.0-1-x86_64.pkg.tar.zst")),
+            (
+                "foo",
+                "1.0-1.1",
+                Some("/cache/foo-1.0-1.1-x86_64.pkg.tar.zst"),
+            ),
+            ("foo", "1.0", None),
+            (
+                "foo-bar",
+                "1.0-1",
+                Some("/cache/foo-bar-1.0-1-any.pkg.tar.xz"),
+            ),
+            ("bar", "1.0-1", None),
+            // epoch
+            (
+                "baz",
+                "2:3.1-2",
+                Some("/cache/baz-2:3.1-2-x86_64.pkg.tar.zst"),
+            ),
+            ("baz", "3.1-2", None),
+            // the arch has no hyphen and is not empty
+            ("qux", "1.0-1", None),
+            ("qux", "1.0", None),
+            // only a signature
+            ("quux", "1.0-1", None),
+        ];
+        for (name, version, expected) in cases {
+            assert_eq!(
+                cache.find(name, &version.parse().unwrap()),
+                expected.map(Path::new),
+                "{name} {version}"
+            );
+        }
+    }
+}
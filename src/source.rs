@@ -0,0 +1,188 @@
+use std::{
+    ffi::OsStr,
+    fmt::{self, Display},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read},
+    os::{fd::AsFd, unix::fs::FileTypeExt},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use anyhow::Result as AnyResult;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+
+use crate::pacman_conf::PacmanConf;
+
+pub const PACMAN_LOG_FILE: &str = "/var/log/pacman.log";
+
+/// Somewhere pacman log lines can be read from
+#[derive(Debug, Clone)]
+pub enum LogSource {
+    /// Plain or compressed (`.gz`, `.xz`, `.zst`) log file
+    File(PathBuf),
+    /// Log piped on standard input
+    Stdin,
+}
+
+impl Display for LogSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Stdin => f.write_str("stdin"),
+        }
+    }
+}
+
+impl LogSource {
+    /// `-` stands for standard input
+    pub fn from_path(path: PathBuf) -> Self {
+        if path.as_os_str() == "-" {
+            Self::Stdin
+        } else {
+            Self::File(path)
+        }
+    }
+
+    /// Standard input if a log is piped or redirected from a file into it
+    ///
+    /// Terminals and devices such as `/dev/null` (cron jobs, systemd units) do not count.
+    pub fn piped_stdin() -> Option<Self> {
+        let stdin = io::stdin().as_fd().try_clone_to_owned().ok()?;
+        let file_type = File::from(stdin).metadata().ok()?.file_type();
+        (file_type.is_fifo() || file_type.is_file()).then_some(Self::Stdin)
+    }
+
+    /// `LogFile` from pacman.conf, falling back to [`PACMAN_LOG_FILE`]
+    pub fn from_conf(conf: &PacmanConf) -> Self {
+        Self::File(
+            conf.log_file
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(PACMAN_LOG_FILE)),
+        )
+    }
+
+    /// Expand a source into its logrotate siblings (`pacman.log.2.gz`, `pacman.log.1`, ...),
+    /// oldest first and followed by the source itself
+    pub fn with_rotated(self) -> Vec<Self> {
+        match &self {
+            Self::File(path) => {
+                let mut sources: Vec<Self> = rotated_files(path)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Self::File)
+                    .collect();
+                sources.push(self);
+                sources
+            }
+            Self::Stdin => vec![self],
+        }
+    }
+
+    /// Open the source as a plain file that can be read from the end, if it is one
+    pub fn open_seekable(&self) -> AnyResult<Option<File>> {
+        match self {
+            Self::File(path) if !is_compressed(path) => File::open(path)
+                .map(Some)
+                .with_context(|| format!("Unable to open `{}`", path.display())),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn open(&self) -> AnyResult<Box<dyn BufRead>> {
+        match self {
+            Self::File(path) => {
+                let file = File::open(path)
+                    .with_context(|| format!("Unable to open `{}`", path.display()))?;
+                let reader: Box<dyn Read> = match path.extension().and_then(OsStr::to_str) {
+                    Some("gz") => Box::new(MultiGzDecoder::new(file)),
+                    Some("xz") => Box::new(XzDecoder::new_multi_decoder(file)),
+                    Some("zst") => Box::new(zstd::Decoder::new(file)?),
+                    _ => Box::new(file),
+                };
+                Ok(Box::new(BufReader::new(reader)))
+            }
+            Self::Stdin => Ok(Box::new(io::stdin().lock())),
+        }
+    }
+}
+
+fn is_compressed(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(OsStr::to_str),
+        Some("gz" | "xz" | "zst")
+    )
+}
+
+/// Rotation index of `candidate` if it is a rotated copy of `log_name`
+/// (`pacman.log.3` or `pacman.log.3.gz` → `3`)
+fn rotation_index(log_name: &str, candidate: &str) -> Option<u32> {
+    let suffix = candidate.strip_prefix(log_name)?.strip_prefix('.')?;
+    let index = match suffix.split_once('.') {
+        Some((index, "gz" | "xz" | "zst")) => index,
+        Some(_) => return None,
+        None => suffix,
+    };
+    index.parse().ok()
+}
+
+/// Rotated copies of `path` sorted from oldest to newest
+fn rotated_files(path: &Path) -> AnyResult<Vec<PathBuf>> {
+    let log_name = path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .context("Log file has no name")?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut rotated = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if let Some(index) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| rotation_index(log_name, name))
+        {
+            rotated.push((index, entry.path()));
+        }
+    }
+
+    // Higher indexes are older
+    rotated.sort_by(|(a, _), (b, _)| b.cmp(a));
+    Ok(rotated.into_iter().map(|(_, path)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_indexes() {
+        let cases = [
+            ("pacman.log.1", Some(1)),
+            ("pacman.log.12", Some(12)),
+            ("pacman.log.2.gz", Some(2)),
+            ("pacman.log.3.xz", Some(3)),
+            ("pacman.log.4.zst", Some(4)),
+            // not rotated copies
+            ("pacman.log", None),
+            ("pacman.log.", None),
+            ("pacman.log.gz", None),
+            ("pacman.log.old", None),
+            ("pacman.log1", None),
+            ("pacman.log.1.bz2", None),
+            ("pacman.log.1.gz.bak", None),
+            ("pacman.log.-1", None),
+            ("other.log.1", None),
+        ];
+        for (candidate, expected) in cases {
+            assert_eq!(
+                rotation_index("pacman.log", candidate),
+                expected,
+                "{candidate}"
+            );
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use anyhow::Result as AnyResult;
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::{
+    date::parse_date,
+    paclog::{PackageChange, PacmanAction},
+    transaction::Transaction,
+    version::Version,
+};
+
+/// Point of the log history a [`PackageState`] can be rebuilt at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogPoint {
+    /// Every change logged at or before this time
+    Date(DateTime<FixedOffset>),
+    /// Every change up to the end of the transaction with this id
+    Transaction(usize),
+}
+
+impl FromStr for LogPoint {
+    type Err = anyhow::Error;
+
+    /// Transaction id (`42` or `#42`) or anything accepted by [`parse_date`]
+    fn from_str(point: &str) -> Result<Self, Self::Err> {
+        match point.strip_prefix('#').unwrap_or(point).parse() {
+            Ok(id) => Ok(Self::Transaction(id)),
+            Err(_) => parse_date(point).map(Self::Date),
+        }
+    }
+}
+
+impl Display for LogPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Date(datetime) => write!(f, "{}", datetime.format("%Y-%m-%d %H:%M:%S")),
+            Self::Transaction(id) => write!(f, "transaction #{id}"),
+        }
+    }
+}
+
+/// Packages installed at some point, rebuilt by replaying [`PackageChange`]s
+///
+/// # Serialization schema
+///
+/// An object mapping each installed package name to its version, sorted by name.
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(transparent)]
+pub struct PackageState {
+    packages: BTreeMap<String, Version>,
+}
+
+impl PackageState {
+    /// Replay `changes` in chronological order on top of an empty system
+    pub fn replay<'a>(changes: impl IntoIterator<Item = &'a PackageChange>) -> Self {
+        let mut state = Self::default();
+        for change in changes {
+            state.apply(change);
+        }
+        state
+    }
+
+    /// Replay `transactions` in log order up to `point`
+    pub fn replay_until<'a>(
+        transactions: impl IntoIterator<Item = &'a Transaction>,
+        point: &LogPoint,
+    ) -> Self {
+        let mut state = Self::default();
+        for transaction in transactions {
+            match (point, transaction.id()) {
+                (LogPoint::Transaction(last), Some(id)) if id > *last => break,
+                (LogPoint::Date(until), _) => {
+                    let changes = transaction.changes().iter();
+                    for change in changes.filter(|change| change.datetime() <= until) {
+                        state.apply(change);
+                    }
+                }
+                _ => {
+                    for change in transaction.changes() {
+                        state.apply(change);
+                    }
+                }
+            }
+        }
+        state
+    }
+
+    pub fn apply(&mut self, change: &PackageChange) {
+        match (change.action(), change.current_version()) {
+            (PacmanAction::Removed, _) => {
+                self.packages.remove(change.name());
+            }
+            (_, Some(version)) => {
+                self.packages
+                    .insert(change.name().to_string(), version.clone());
+            }
+            (_, None) => {}
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Version> {
+        self.packages.get(name)
+    }
+
+    /// Installed packages and their version, sorted by name
+    pub fn packages(&self) -> impl Iterator<Item = (&str, &Version)> {
+        self.packages
+            .iter()
+            .map(|(name, version)| (name.as_str(), version))
+    }
+
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+}
+
+impl PackageState {
+    /// Write one `name version` line per package, or only the names as expected by
+    /// `pacman -S --needed -` if `names_only`
+    pub fn render<W: WriteColor>(&self, out: &mut W, names_only: bool) -> AnyResult<()> {
+        let width = self
+            .packages
+            .keys()
+            .map(String::len)
+            .max()
+            .unwrap_or_default();
+        for (name, version) in self.packages() {
+            if names_only {
+                out.write_all(format!("{name}\n").as_bytes())?;
+                continue;
+            }
+
+            out.set_color(
+                ColorSpec::new()
+                    .set_fg(Some(Color::Yellow))
+                    .set_bold(true)
+                    .set_intense(true),
+            )?;
+            out.write_all(format!("{name:width$}").as_bytes())?;
+
+            out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+            out.write_all(format!("  {version}").as_bytes())?;
+
+            out.reset()?;
+            out.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+}
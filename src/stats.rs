@@ -0,0 +1,373 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use anyhow::Result as AnyResult;
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::{paclog::PacmanAction, transaction::Transaction};
+
+/// Length of the periods upgrades are counted over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    /// `2021-03`
+    Month,
+    /// ISO week, `2021-W09`
+    Week,
+}
+
+impl Period {
+    fn key(&self, datetime: &DateTime<FixedOffset>) -> String {
+        match self {
+            Self::Month => datetime.format("%Y-%m").to_string(),
+            Self::Week => datetime.format("%G-W%V").to_string(),
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = anyhow::Error;
+
+    fn from_str(period: &str) -> Result<Self, Self::Err> {
+        match period {
+            "month" => Ok(Self::Month),
+            "week" => Ok(Self::Week),
+            _ => Err(anyhow!("`{period}` is not a valid period!")),
+        }
+    }
+}
+
+/// Number of changes per [`PacmanAction`]
+#[derive(Debug, Default, Serialize)]
+pub struct ActionCounts {
+    pub installed: usize,
+    pub upgraded: usize,
+    pub downgraded: usize,
+    pub removed: usize,
+    pub reinstalled: usize,
+}
+
+impl ActionCounts {
+    pub fn get(&self, action: PacmanAction) -> usize {
+        match action {
+            PacmanAction::Installed => self.installed,
+            PacmanAction::Upgraded => self.upgraded,
+            PacmanAction::Downgraded => self.downgraded,
+            PacmanAction::Removed => self.removed,
+            PacmanAction::Reinstalled => self.reinstalled,
+        }
+    }
+
+    fn count(&mut self, action: PacmanAction) {
+        *match action {
+            PacmanAction::Installed => &mut self.installed,
+            PacmanAction::Upgraded => &mut self.upgraded,
+            PacmanAction::Downgraded => &mut self.downgraded,
+            PacmanAction::Removed => &mut self.removed,
+            PacmanAction::Reinstalled => &mut self.reinstalled,
+        } += 1;
+    }
+}
+
+/// Upgrade activity of a single package
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageActivity {
+    name: String,
+    upgrades: usize,
+    first_seen: DateTime<FixedOffset>,
+    last_seen: DateTime<FixedOffset>,
+}
+
+impl PackageActivity {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn upgrades(&self) -> usize {
+        self.upgrades
+    }
+
+    pub fn first_seen(&self) -> &DateTime<FixedOffset> {
+        &self.first_seen
+    }
+
+    pub fn last_seen(&self) -> &DateTime<FixedOffset> {
+        &self.last_seen
+    }
+}
+
+/// Number of upgrades logged during a period
+#[derive(Debug, Serialize)]
+pub struct PeriodCount {
+    period: String,
+    upgrades: usize,
+}
+
+impl PeriodCount {
+    pub fn period(&self) -> &str {
+        &self.period
+    }
+
+    pub fn upgrades(&self) -> usize {
+        self.upgrades
+    }
+}
+
+/// Overview of the package activity of a log
+///
+/// # Serialization schema
+///
+/// | Field                      | Type           | Description                                  |
+/// |----------------------------|----------------|----------------------------------------------|
+/// | `changes`                  | integer        | Number of package changes                    |
+/// | `actions`                  | object         | Number of changes per action                 |
+/// | `transactions`             | integer        | Number of transactions with a change         |
+/// | `average_transaction_size` | number         | Average number of changes per transaction    |
+/// | `first_seen`               | string \| null | RFC 3339 timestamp of the oldest change      |
+/// | `last_seen`                | string \| null | RFC 3339 timestamp of the most recent change |
+/// | `most_upgraded`            | array          | Most upgraded packages, most upgrades first  |
+/// | `upgrades_per_period`      | array          | Upgrades per month or week, oldest first     |
+///
+/// A `most_upgraded` entry has a `name`, a number of `upgrades` and the `first_seen` and
+/// `last_seen` timestamps of the package. A `upgrades_per_period` entry has a `period`
+/// (`2021-03` or `2021-W09`) and a number of `upgrades`, periods without upgrades are left out.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    changes: usize,
+    actions: ActionCounts,
+    transactions: usize,
+    average_transaction_size: f64,
+    first_seen: Option<DateTime<FixedOffset>>,
+    last_seen: Option<DateTime<FixedOffset>>,
+    most_upgraded: Vec<PackageActivity>,
+    #[serde(skip)]
+    period: Period,
+    upgrades_per_period: Vec<PeriodCount>,
+}
+
+impl Stats {
+    /// Compute the statistics of `transactions`, keeping the `top` most upgraded packages
+    pub fn new(transactions: &[Transaction], period: Period, top: usize) -> Self {
+        let mut actions = ActionCounts::default();
+        let mut packages: HashMap<&str, PackageActivity> = HashMap::new();
+        let mut periods: BTreeMap<String, usize> = BTreeMap::new();
+        let (mut first_seen, mut last_seen) = (None, None);
+
+        let changes = transactions.iter().flat_map(Transaction::changes);
+        for change in changes.clone() {
+            let datetime = *change.datetime();
+            actions.count(change.action());
+            first_seen =
+                Some(first_seen.map_or(datetime, |first: DateTime<_>| first.min(datetime)));
+            last_seen = Some(last_seen.map_or(datetime, |last: DateTime<_>| last.max(datetime)));
+
+            let activity = packages
+                .entry(change.name())
+                .or_insert_with(|| PackageActivity {
+                    name: change.name().to_string(),
+                    upgrades: 0,
+                    first_seen: datetime,
+                    last_seen: datetime,
+                });
+            activity.first_seen = activity.first_seen.min(datetime);
+            activity.last_seen = activity.last_seen.max(datetime);
+
+            if change.action() == PacmanAction::Upgraded {
+                activity.upgrades += 1;
+                *periods.entry(period.key(&datetime)).or_default() += 1;
+            }
+        }
+
+        let mut most_upgraded: Vec<PackageActivity> = packages
+            .into_values()
+            .filter(|activity| activity.upgrades > 0)
+            .collect();
+        most_upgraded.sort_by(|a, b| b.upgrades.cmp(&a.upgrades).then(a.name.cmp(&b.name)));
+        most_upgraded.truncate(top);
+
+        // Changes logged outside of any transaction would skew the average
+        let sizes: Vec<usize> = transactions
+            .iter()
+            .filter(|transaction| transaction.id().is_some())
+            .map(|transaction| transaction.changes().len())
+            .collect();
+        let average_transaction_size = if sizes.is_empty() {
+            0.0
+        } else {
+            sizes.iter().sum::<usize>() as f64 / sizes.len() as f64
+        };
+
+        Self {
+            changes: changes.count(),
+            actions,
+            transactions: sizes.len(),
+            average_transaction_size,
+            first_seen,
+            last_seen,
+            most_upgraded,
+            period,
+            upgrades_per_period: periods
+                .into_iter()
+                .map(|(period, upgrades)| PeriodCount { period, upgrades })
+                .collect(),
+        }
+    }
+
+    pub fn changes(&self) -> usize {
+        self.changes
+    }
+
+    pub fn actions(&self) -> &ActionCounts {
+        &self.actions
+    }
+
+    /// Number of transactions with at least a change
+    pub fn transactions(&self) -> usize {
+        self.transactions
+    }
+
+    pub fn average_transaction_size(&self) -> f64 {
+        self.average_transaction_size
+    }
+
+    pub fn first_seen(&self) -> Option<&DateTime<FixedOffset>> {
+        self.first_seen.as_ref()
+    }
+
+    pub fn last_seen(&self) -> Option<&DateTime<FixedOffset>> {
+        self.last_seen.as_ref()
+    }
+
+    pub fn most_upgraded(&self) -> &[PackageActivity] {
+        &self.most_upgraded
+    }
+
+    pub fn upgrades_per_period(&self) -> &[PeriodCount] {
+        &self.upgrades_per_period
+    }
+}
+
+impl Stats {
+    pub fn render<W: WriteColor>(&self, out: &mut W) -> AnyResult<()> {
+        /// Width of the longest bar of the upgrades per period histogram
+        const BAR_WIDTH: usize = 40;
+
+        let format_datetime = |datetime: Option<&DateTime<FixedOffset>>| {
+            datetime.map_or(String::from("?"), |datetime| {
+                datetime.format("%Y-%m-%d %H:%M").to_string()
+            })
+        };
+
+        out.write_all(
+            format!(
+                "{} changes in {} transactions ({:.1} changes per transaction on average)\n",
+                self.changes(),
+                self.transactions(),
+                self.average_transaction_size()
+            )
+            .as_bytes(),
+        )?;
+        out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+        out.write_all(
+            format!(
+                "First seen {}, last seen {}\n",
+                format_datetime(self.first_seen()),
+                format_datetime(self.last_seen())
+            )
+            .as_bytes(),
+        )?;
+        out.reset()?;
+
+        out.set_color(ColorSpec::new().set_bold(true))?;
+        out.write_all(b"\nActions\n")?;
+        out.reset()?;
+        for (action, color) in [
+            (PacmanAction::Installed, Color::Green),
+            (PacmanAction::Upgraded, Color::Cyan),
+            (PacmanAction::Downgraded, Color::Magenta),
+            (PacmanAction::Removed, Color::Red),
+            (PacmanAction::Reinstalled, Color::Blue),
+        ] {
+            out.set_color(ColorSpec::new().set_fg(Some(color)))?;
+            out.write_all(format!("    {:11}", action.to_string()).as_bytes())?;
+            out.reset()?;
+            out.write_all(format!("  {}\n", self.actions().get(action)).as_bytes())?;
+        }
+
+        if !self.most_upgraded().is_empty() {
+            out.set_color(ColorSpec::new().set_bold(true))?;
+            out.write_all(b"\nMost upgraded\n")?;
+            out.reset()?;
+
+            let width = self
+                .most_upgraded()
+                .iter()
+                .map(|activity| activity.name().len())
+                .max()
+                .unwrap_or_default();
+            let count_width = self.most_upgraded()[0].upgrades().to_string().len();
+            for activity in self.most_upgraded() {
+                out.set_color(
+                    ColorSpec::new()
+                        .set_fg(Some(Color::Yellow))
+                        .set_bold(true)
+                        .set_intense(true),
+                )?;
+                out.write_all(format!("    {:width$}", activity.name()).as_bytes())?;
+                out.reset()?;
+                out.write_all(format!("  {:>count_width$}", activity.upgrades()).as_bytes())?;
+
+                out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+                out.write_all(
+                    format!(
+                        "  {} -> {}",
+                        format_datetime(Some(activity.first_seen())),
+                        format_datetime(Some(activity.last_seen()))
+                    )
+                    .as_bytes(),
+                )?;
+                out.reset()?;
+                out.write_all(b"\n")?;
+            }
+        }
+
+        if !self.upgrades_per_period().is_empty() {
+            out.set_color(ColorSpec::new().set_bold(true))?;
+            let title = match self.period {
+                Period::Month => "\nUpgrades per month\n",
+                Period::Week => "\nUpgrades per week\n",
+            };
+            out.write_all(title.as_bytes())?;
+            out.reset()?;
+
+            let max = self
+                .upgrades_per_period()
+                .iter()
+                .map(PeriodCount::upgrades)
+                .max()
+                .unwrap_or_default();
+            let count_width = max.to_string().len();
+            for count in self.upgrades_per_period() {
+                out.write_all(
+                    format!(
+                        "    {:8}  {:>count_width$}  ",
+                        count.period(),
+                        count.upgrades()
+                    )
+                    .as_bytes(),
+                )?;
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+                let bar = (count.upgrades() * BAR_WIDTH).div_ceil(max);
+                out.write_all("#".repeat(bar).as_bytes())?;
+                out.reset()?;
+                out.write_all(b"\n")?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,371 @@
+use std::{io::BufRead, slice};
+
+use anyhow::Context;
+use anyhow::Result as AnyResult;
+use chrono::{DateTime, FixedOffset};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::{
+    error::{CorruptLine, LineError},
+    paclog::{ends_command, parse_command, parse_datetime, ChangeFilter, LogLines, PackageChange},
+    source::LogSource,
+};
+
+lazy_static! {
+    static ref TRANSACTION_REGEX: Regex = Regex::new(
+        r"^\[(?P<date>[^\]]*)\] \[ALPM\] transaction (?P<event>started|completed|failed|interrupted)$"
+    )
+    .unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionStatus {
+    Completed,
+    Failed,
+    Interrupted,
+    /// No closing marker: pacman crashed, is still running, or the log predates transaction markers
+    Incomplete,
+}
+
+/// Package changes logged between `transaction started` and its closing marker
+///
+/// # Serialization schema
+///
+/// | Field     | Type             | Description                                                  |
+/// |-----------|------------------|--------------------------------------------------------------|
+/// | `id`      | integer \| null  | Position among the log's transactions, `null` outside of any |
+/// | `started` | string \| null   | RFC 3339 timestamp of `transaction started`                  |
+/// | `ended`   | string \| null   | RFC 3339 timestamp of the closing marker                     |
+/// | `status`  | string           | `completed`, `failed`, `interrupted` or `incomplete`         |
+/// | `command` | string \| null   | pacman command line that started the transaction             |
+/// | `changes` | array            | Package changes of the transaction                           |
+#[derive(Debug, Serialize)]
+pub struct Transaction {
+    /// 1-based position of the transaction among the `transaction started` markers of the log
+    /// sources, `None` for changes logged outside of any transaction
+    id: Option<usize>,
+    started: Option<DateTime<FixedOffset>>,
+    ended: Option<DateTime<FixedOffset>>,
+    status: TransactionStatus,
+    /// pacman command line that started the transaction
+    command: Option<String>,
+    changes: Vec<PackageChange>,
+}
+
+impl Transaction {
+    fn new(
+        id: Option<usize>,
+        started: Option<DateTime<FixedOffset>>,
+        command: Option<String>,
+    ) -> Self {
+        Self {
+            id,
+            started,
+            ended: None,
+            status: TransactionStatus::Incomplete,
+            command,
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn id(&self) -> Option<usize> {
+        self.id
+    }
+
+    pub fn started(&self) -> Option<&DateTime<FixedOffset>> {
+        self.started.as_ref()
+    }
+
+    pub fn ended(&self) -> Option<&DateTime<FixedOffset>> {
+        self.ended.as_ref()
+    }
+
+    pub fn status(&self) -> TransactionStatus {
+        self.status
+    }
+
+    pub fn command(&self) -> Option<&str> {
+        self.command.as_deref()
+    }
+
+    pub fn changes(&self) -> &[PackageChange] {
+        &self.changes
+    }
+
+    pub fn into_changes(self) -> Vec<PackageChange> {
+        self.changes
+    }
+}
+
+impl Transaction {
+    pub fn render<W: WriteColor>(&self, out: &mut W) -> AnyResult<()> {
+        out.set_color(ColorSpec::new().set_bold(true))?;
+        match self.id() {
+            Some(id) => out.write_all(format!("Transaction #{id}").as_bytes())?,
+            None => out.write_all(b"Outside of any transaction")?,
+        }
+
+        out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+        let format_datetime = |datetime: Option<&DateTime<FixedOffset>>| {
+            datetime.map_or(String::from("?"), |datetime| {
+                datetime.format("%Y-%m-%d %H:%M:%S").to_string()
+            })
+        };
+        out.write_all(
+            format!(
+                " [{} -> {}] ",
+                format_datetime(self.started()),
+                format_datetime(self.ended())
+            )
+            .as_bytes(),
+        )?;
+
+        match self.status() {
+            TransactionStatus::Completed => {
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+                out.write_all(b"completed")?;
+            }
+            TransactionStatus::Failed => {
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
+                out.write_all(b"failed")?;
+            }
+            TransactionStatus::Interrupted => {
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
+                out.write_all(b"interrupted")?;
+            }
+            TransactionStatus::Incomplete => {
+                out.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+                out.write_all(b"incomplete")?;
+            }
+        }
+
+        if let Some(command) = self.command() {
+            out.set_color(ColorSpec::new().set_fg(Some(Color::White)).set_dimmed(true))?;
+            out.write_all(format!(" via '{command}'").as_bytes())?;
+        }
+
+        out.reset()?;
+        out.write_all(b"\n")?;
+
+        for change in self.changes() {
+            change.render_with_prefix(out, "    ", false)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Streams the [`Transaction`]s of a set of log sources read one after the other
+///
+/// Changes logged outside of any transaction markers are grouped into an [`Incomplete`]
+/// transaction without id. Transactions without any change matching the filter are skipped.
+/// Corrupt package changes are handled as by [`ChangeReader`](crate::ChangeReader).
+///
+/// [`Incomplete`]: TransactionStatus::Incomplete
+pub struct TransactionReader<'a> {
+    sources: slice::Iter<'a, LogSource>,
+    /// Source being read and its lines
+    lines: Option<(&'a LogSource, LogLines<Box<dyn BufRead>>)>,
+    filter: &'a ChangeFilter,
+    strict: bool,
+    command: Option<String>,
+    current: Option<Transaction>,
+    next_id: usize,
+}
+
+impl<'a> TransactionReader<'a> {
+    pub fn new(sources: &'a [LogSource], filter: &'a ChangeFilter) -> Self {
+        Self {
+            sources: sources.iter(),
+            lines: None,
+            filter,
+            strict: false,
+            command: None,
+            current: None,
+            next_id: 1,
+        }
+    }
+
+    pub fn strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+
+    /// Hand over a finished transaction, unless it has no change left after filtering
+    fn finish(transaction: Option<Transaction>) -> Option<Transaction> {
+        transaction.filter(|transaction| !transaction.changes.is_empty())
+    }
+
+    /// Process a line, returning a transaction it closes or the error it triggers
+    fn process_line(&mut self, line: String, line_number: usize) -> Option<AnyResult<Transaction>> {
+        if let Some(running) = parse_command(&line) {
+            self.command = Some(running);
+            return None;
+        }
+        if ends_command(&line) {
+            // Closing markers are still processed below
+            self.command = None;
+        }
+
+        if let Some(cap) = TRANSACTION_REGEX.captures(&line) {
+            let datetime = parse_datetime(&cap["date"]).ok();
+            if &cap["event"] == "started" {
+                let previous = self.current.replace(Transaction::new(
+                    Some(self.next_id),
+                    datetime,
+                    self.command.clone(),
+                ));
+                self.next_id += 1;
+                return Self::finish(previous).map(Ok);
+            }
+
+            let mut transaction = self.current.take()?;
+            transaction.ended = datetime;
+            transaction.status = match &cap["event"] {
+                "completed" => TransactionStatus::Completed,
+                "failed" => TransactionStatus::Failed,
+                _ => TransactionStatus::Interrupted,
+            };
+            return Self::finish(Some(transaction)).map(Ok);
+        }
+
+        if !self.filter.command_matches(self.command.as_deref()) {
+            return None;
+        }
+        match PackageChange::from_line(line.clone(), self.filter) {
+            Ok(change) => {
+                let command = self.command.clone();
+                self.current
+                    .get_or_insert_with(|| Transaction::new(None, None, command.clone()))
+                    .changes
+                    .push(change.with_command(command));
+                None
+            }
+            Err(LineError::Corrupt(reason)) if self.strict => Some(Err(CorruptLine {
+                line_number,
+                line,
+                reason,
+            }
+            .into())),
+            Err(_) => None,
+        }
+    }
+}
+
+impl Iterator for TransactionReader<'_> {
+    type Item = AnyResult<Transaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some((source, lines)) = &mut self.lines else {
+                let Some(source) = self.sources.next() else {
+                    return Self::finish(self.current.take()).map(Ok);
+                };
+                match source.open() {
+                    Ok(reader) => {
+                        self.lines = Some((source, LogLines::new(reader)));
+                        self.command = None;
+                        continue;
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+            };
+
+            let source: &LogSource = source;
+            let (line, line_number) = match lines.next() {
+                Some(Ok(line)) => (line, lines.line_number),
+                Some(Err(e)) => {
+                    return Some(Err(anyhow::Error::from(e).context(source.to_string())))
+                }
+                None => {
+                    self.lines = None;
+                    continue;
+                }
+            };
+
+            if let Some(result) = self.process_line(line, line_number) {
+                return Some(result.with_context(|| source.to_string()));
+            }
+        }
+    }
+}
+
+/// Collect the transactions of every source, see [`TransactionReader`] to stream them instead
+pub fn get_transactions(
+    sources: &[LogSource],
+    filter: &ChangeFilter,
+) -> AnyResult<Vec<Transaction>> {
+    TransactionReader::new(sources, filter).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use termcolor::Buffer;
+
+    use super::*;
+
+    /// A `pacman -Sy` without transaction followed by a pamac upgrade, then a pacman
+    /// transaction followed by one from a front-end that logs nothing
+    const FOREIGN_LOG: &str = "\
+[2021-03-04T12:00:00+0100] [PACMAN] Running 'pacman -Sy'
+[2021-03-04T12:00:00+0100] [PACMAN] synchronizing package lists
+[2021-03-04T12:05:00+0100] [PAMAC] synchronizing package lists
+[2021-03-04T12:05:01+0100] [ALPM] transaction started
+[2021-03-04T12:05:01+0100] [ALPM] upgraded foo (1.0-1 -> 1.1-1)
+[2021-03-04T12:05:02+0100] [ALPM] transaction completed
+[2021-03-05T09:00:00+0100] [PACMAN] Running 'pacman -S bar'
+[2021-03-05T09:00:00+0100] [ALPM] transaction started
+[2021-03-05T09:00:00+0100] [ALPM] installed bar (1.0-1)
+[2021-03-05T09:00:01+0100] [ALPM] transaction completed
+[2021-03-06T09:00:00+0100] [ALPM] transaction started
+[2021-03-06T09:00:00+0100] [ALPM] installed baz (1.0-1)
+[2021-03-06T09:00:01+0100] [ALPM] transaction completed
+";
+
+    #[test]
+    fn render_without_color() {
+        let started = parse_datetime("2021-03-04T12:00:01+0100").ok();
+        let mut transaction =
+            Transaction::new(Some(3), started, Some(String::from("pacman -S foo")));
+        transaction.ended = parse_datetime("2021-03-04T12:00:02+0100").ok();
+        transaction.status = TransactionStatus::Completed;
+        let line = "[2021-03-04T12:00:01+0100] [ALPM] installed foo (1.0-1)";
+        transaction
+            .changes
+            .push(PackageChange::from_line(line.to_string(), &ChangeFilter::default()).unwrap());
+
+        let mut out = Buffer::no_color();
+        transaction.render(&mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out.into_inner()).unwrap(),
+            "Transaction #3 [2021-03-04 12:00:01 -> 2021-03-04 12:00:02] completed via 'pacman -S foo'\n    \
+             [2021-03-04T12:00:01+0100] installed foo (1.0-1)\n"
+        );
+    }
+
+    #[test]
+    fn command_does_not_outlive_its_scope() {
+        let filter = ChangeFilter::default();
+        let mut reader = TransactionReader::new(&[], &filter);
+        let mut transactions: Vec<Transaction> = FOREIGN_LOG
+            .lines()
+            .enumerate()
+            .filter_map(|(index, line)| reader.process_line(line.to_string(), index + 1))
+            .collect::<AnyResult<_>>()
+            .unwrap();
+        transactions.extend(TransactionReader::finish(reader.current.take()));
+
+        let commands: Vec<Option<&str>> = transactions.iter().map(Transaction::command).collect();
+        assert_eq!(commands, [None, Some("pacman -S bar"), None]);
+        let change_commands: Vec<Option<&str>> = transactions
+            .iter()
+            .flat_map(Transaction::changes)
+            .map(PackageChange::command)
+            .collect();
+        assert_eq!(change_commands, [None, Some("pacman -S bar"), None]);
+    }
+}
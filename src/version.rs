@@ -0,0 +1,313 @@
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use serde::{Serialize, Serializer};
+
+/// Package version in pacman's `[epoch:]pkgver[-pkgrel]` format
+///
+/// Keeps the text it was parsed from, which is what it displays and serializes as. Versions are
+/// ordered the way `vercmp` orders them, so `1.0` and `1.00` or `1.0` and `1.0-2` are equal.
+///
+/// That ordering is not transitive when only one side has a pkgrel: `1.0-1 == 1.0 == 1.0-2` but
+/// `1.0-1 < 1.0-2`. Use it to compare two versions, never as a sort or map key.
+#[derive(Debug, Clone)]
+pub struct Version {
+    raw: String,
+    epoch: Option<String>,
+    pkgver: String,
+    pkgrel: Option<String>,
+}
+
+impl Version {
+    /// Epoch, if the version has one
+    pub fn epoch(&self) -> Option<&str> {
+        self.epoch.as_deref()
+    }
+
+    pub fn pkgver(&self) -> &str {
+        &self.pkgver
+    }
+
+    /// Release, if the version has one
+    pub fn pkgrel(&self) -> Option<&str> {
+        self.pkgrel.as_deref()
+    }
+
+    /// Version exactly as written in the log
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    /// Split like libalpm's `parseEVR`: the epoch is the leading digits before the first `:`
+    /// and the release is whatever follows the last `-`
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        if raw.is_empty() || raw.contains(char::is_whitespace) {
+            return Err(anyhow!("`{raw}` is not a valid version"));
+        }
+
+        let (epoch, rest) = match raw.split_once(':') {
+            Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => (Some(epoch), rest),
+            _ => (None, raw),
+        };
+        let (pkgver, pkgrel) = match rest.rsplit_once('-') {
+            Some((pkgver, pkgrel)) => (pkgver, Some(pkgrel)),
+            None => (rest, None),
+        };
+
+        if epoch == Some("")
+            || pkgver.is_empty()
+            || pkgver.contains([':', '-', '/'])
+            || pkgrel == Some("")
+        {
+            return Err(anyhow!("`{raw}` is not a valid version"));
+        }
+
+        Ok(Self {
+            raw: raw.to_string(),
+            epoch: epoch.map(String::from),
+            pkgver: pkgver.to_string(),
+            pkgrel: pkgrel.map(String::from),
+        })
+    }
+}
+
+fn trim_leading_zeros(segment: &[u8]) -> &[u8] {
+    let zeros = segment.iter().take_while(|&&c| c == b'0').count();
+    &segment[zeros..]
+}
+
+/// libalpm's `rpmvercmp`: compare alternating runs of digits and letters, ignoring separators
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut one, mut two) = (0, 0);
+
+    while one < a.len() && two < b.len() {
+        let (separator_start_one, separator_start_two) = (one, two);
+        while one < a.len() && !a[one].is_ascii_alphanumeric() {
+            one += 1;
+        }
+        while two < b.len() && !b[two].is_ascii_alphanumeric() {
+            two += 1;
+        }
+
+        if one == a.len() || two == b.len() {
+            break;
+        }
+
+        // The longer separator wins
+        let (separator_one, separator_two) = (one - separator_start_one, two - separator_start_two);
+        if separator_one != separator_two {
+            return separator_one.cmp(&separator_two);
+        }
+
+        // Segments are runs of the same kind of characters as the one starting the first one
+        let is_num = a[one].is_ascii_digit();
+        let same_kind = |c: &u8| {
+            if is_num {
+                c.is_ascii_digit()
+            } else {
+                c.is_ascii_alphabetic()
+            }
+        };
+        let end_one = one + a[one..].iter().take_while(|c| same_kind(c)).count();
+        let end_two = two + b[two..].iter().take_while(|c| same_kind(c)).count();
+
+        // Numbers are newer than letters
+        if end_two == two {
+            return if is_num {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let (mut segment_one, mut segment_two) = (&a[one..end_one], &b[two..end_two]);
+        if is_num {
+            segment_one = trim_leading_zeros(segment_one);
+            segment_two = trim_leading_zeros(segment_two);
+
+            let by_length = segment_one.len().cmp(&segment_two.len());
+            if by_length != Ordering::Equal {
+                return by_length;
+            }
+        }
+
+        let by_content = segment_one.cmp(segment_two);
+        if by_content != Ordering::Equal {
+            return by_content;
+        }
+
+        one = end_one;
+        two = end_two;
+    }
+
+    let (rest_one, rest_two) = (a.get(one), b.get(two));
+    match (rest_one, rest_two) {
+        (None, None) => Ordering::Equal,
+        // A remaining alpha string never beats an empty string
+        (None, Some(c)) if !c.is_ascii_alphabetic() => Ordering::Less,
+        (Some(c), _) if c.is_ascii_alphabetic() => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
+
+impl Ord for Version {
+    /// libalpm's `alpm_pkg_vercmp`: compare epochs, then pkgvers, then pkgrels if both have one
+    ///
+    /// Not a total order, see [`Version`].
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.raw == other.raw {
+            return Ordering::Equal;
+        }
+
+        rpmvercmp(self.epoch().unwrap_or("0"), other.epoch().unwrap_or("0"))
+            .then_with(|| rpmvercmp(&self.pkgver, &other.pkgver))
+            .then_with(|| match (self.pkgrel(), other.pkgrel()) {
+                (Some(rel_one), Some(rel_two)) => rpmvercmp(rel_one, rel_two),
+                _ => Ordering::Equal,
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vercmp(a: &str, b: &str) -> Ordering {
+        a.parse::<Version>()
+            .unwrap()
+            .cmp(&b.parse::<Version>().unwrap())
+    }
+
+    /// Vectors from pacman's `test/util/vercmptest.sh`, each checked both ways
+    #[test]
+    fn vercmptest_vectors() {
+        use Ordering::{Equal, Greater, Less};
+
+        let cases = [
+            // all similar length, no pkgrel
+            ("1.5.0", "1.5.0", Equal),
+            ("1.5.1", "1.5.0", Greater),
+            // mixed length
+            ("1.5.1", "1.5", Greater),
+            // with pkgrel, simple
+            ("1.5.0-1", "1.5.0-1", Equal),
+            ("1.5.0-1", "1.5.0-2", Less),
+            ("1.5.0-1", "1.5.1-1", Less),
+            ("1.5.0-2", "1.5.1-1", Less),
+            // with pkgrel, mixed lengths
+            ("1.5-1", "1.5.1-1", Less),
+            ("1.5-2", "1.5.1-1", Less),
+            ("1.5-2", "1.5.1-2", Less),
+            // mixed pkgrel inclusion
+            ("1.5", "1.5-1", Equal),
+            ("1.5-1", "1.5", Equal),
+            ("1.1-1", "1.1", Equal),
+            ("1.0-1", "1.1", Less),
+            ("1.1-1", "1.0", Greater),
+            // alphanumeric versions
+            ("1.5b-1", "1.5-1", Less),
+            ("1.5b", "1.5", Less),
+            ("1.5b-1", "1.5", Less),
+            ("1.5b", "1.5.1", Less),
+            // from the manpage
+            ("1.0a", "1.0alpha", Less),
+            ("1.0alpha", "1.0b", Less),
+            ("1.0b", "1.0beta", Less),
+            ("1.0beta", "1.0rc", Less),
+            ("1.0rc", "1.0", Less),
+            // alpha-dotted versions
+            ("1.5.a", "1.5", Greater),
+            ("1.5.b", "1.5.a", Greater),
+            ("1.5.1", "1.5.b", Greater),
+            // alpha dots and dashes
+            ("1.5.b-1", "1.5.b", Equal),
+            ("1.5-1", "1.5.b", Less),
+            // same/similar content, differing separators
+            ("2.0", "2_0", Equal),
+            ("2.0_a", "2_0.a", Equal),
+            ("2.0a", "2.0.a", Less),
+            ("2___a", "2_a", Greater),
+            // epoch included version comparisons
+            ("0:1.0", "0:1.0", Equal),
+            ("0:1.0", "0:1.1", Less),
+            ("1:1.0", "0:1.0", Greater),
+            ("1:1.0", "0:1.1", Greater),
+            ("1:1.0", "2:1.1", Less),
+            // epoch + sometimes present pkgrel
+            ("1:1.0", "0:1.0-1", Greater),
+            ("1:1.0-1", "0:1.1-1", Greater),
+            // epoch included on one version
+            ("0:1.0", "1.0", Equal),
+            ("0:1.0", "1.1", Less),
+            ("0:1.1", "1.0", Greater),
+            ("1:1.0", "1.0", Greater),
+            ("1:1.0", "1.1", Greater),
+            ("1:1.1", "1.1", Greater),
+        ];
+
+        for (a, b, expected) in cases {
+            assert_eq!(vercmp(a, b), expected, "vercmp {a} {b}");
+            assert_eq!(vercmp(b, a), expected.reverse(), "vercmp {b} {a}");
+        }
+    }
+
+    #[test]
+    fn leading_zeros_are_ignored() {
+        assert_eq!(vercmp("1.001", "1.1"), Ordering::Equal);
+        assert_eq!(vercmp("1.010", "1.9"), Ordering::Greater);
+        assert_eq!(vercmp("1.0-01", "1.0-1"), Ordering::Equal);
+    }
+
+    #[test]
+    fn trailing_alpha_and_dotted_alpha() {
+        assert_eq!(vercmp("1.0", "1.0a"), Ordering::Greater);
+        assert_eq!(vercmp("1.0", "1.0.a"), Ordering::Less);
+        assert_eq!(vercmp("1.0a", "1.0.a"), Ordering::Less);
+    }
+
+    #[test]
+    fn pkgrel_on_one_side_is_not_transitive() {
+        assert_eq!(vercmp("1.0-1", "1.0"), Ordering::Equal);
+        assert_eq!(vercmp("1.0", "1.0-2"), Ordering::Equal);
+        assert_eq!(vercmp("1.0-1", "1.0-2"), Ordering::Less);
+    }
+}